@@ -0,0 +1,342 @@
+use {
+    async_trait::async_trait,
+    carbon_core::{
+        account::{AccountMetadata, DecodedAccount},
+        deserialize::CarbonDeserialize,
+        error::CarbonResult,
+        metrics::MetricsCollection,
+        processor::Processor,
+    },
+    carbon_meteora_dlmm_decoder::{
+        PROGRAM_ID as METEORA_PROGRAM_ID,
+        accounts::{
+            MeteoraDlmmAccount, bin_array::BinArray, lb_pair::LbPair, position_v2::PositionV2,
+        },
+        types::Bin,
+    },
+    carbon_rpc_program_subscribe_datasource::Filters,
+    log::{debug, warn},
+    serde::Serialize,
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+    std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    },
+};
+
+use crate::{rpc_pool::RpcPool, utils::LP_WALLETS};
+
+/// Number of bins stored in a single `BinArray` account
+pub const MAX_BIN_PER_ARRAY: i32 = 70;
+/// Fee rates of the DLMM program are expressed with 9 decimals
+const FEE_PRECISION: u128 = 1_000_000_000;
+/// Account sizes, discriminator included
+const LB_PAIR_LEN: u64 = 904;
+const BIN_ARRAY_LEN: u64 = 10_136;
+const POSITION_V2_LEN: u64 = 8_120;
+/// Offset of `owner` in `PositionV2`: discriminator then `lb_pair`
+const POSITION_OWNER_OFFSET: usize = 40;
+/// Offset of `lb_pair` in `BinArray`: discriminator, `index`, `version` and padding
+const BIN_ARRAY_LB_PAIR_OFFSET: usize = 24;
+
+/// Index of the bin array holding `bin_id`
+pub fn bin_array_index(bin_id: i32) -> i64 {
+    bin_id.div_euclid(MAX_BIN_PER_ARRAY) as i64
+}
+
+/// Base fee in basis points of a pool with these fee parameters
+pub fn base_fee_bps(base_factor: u16, bin_step: u16, base_fee_power_factor: u8) -> f64 {
+    let base_fee_rate =
+        base_factor as u128 * bin_step as u128 * 10 * 10u128.pow(base_fee_power_factor as u32);
+    base_fee_rate as f64 * 10_000.0 / FEE_PRECISION as f64
+}
+
+/// `LbPair` account of `lb_pair` fetched at confirmed commitment, for pools missing
+/// from the account stream. `None` without RPC access or when the fetch fails.
+pub async fn fetch_pool(lb_pair: &Pubkey) -> Option<LbPair> {
+    let rpc_pool = RpcPool::try_global()?;
+    let response = rpc_pool
+        .call(|rpc_client| async move {
+            rpc_client
+                .get_account_with_commitment(lb_pair, CommitmentConfig::confirmed())
+                .await
+        })
+        .await;
+    match response {
+        Ok(response) => LbPair::deserialize(&response.value?.data),
+        Err(e) => {
+            warn!("Failed to fetch pool {}: {}", lb_pair, e);
+            None
+        }
+    }
+}
+
+/// Account state paired with the slot it was observed at
+#[derive(Debug, Clone)]
+struct Versioned<T> {
+    slot: u64,
+    value: T,
+}
+
+/// Current state of a pool as seen on the account stream
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolSummary {
+    pub active_id: i32,
+    pub bin_step: u16,
+    pub base_fee_bps: f64,
+    pub protocol_share_bps: u16,
+    /// Reserves of the active bin, `None` until its bin array has been streamed
+    pub active_bin_amounts: Option<(u64, u64)>,
+}
+
+/// Latest `LbPair`, `BinArray` and `PositionV2` states received from the program subscription.
+///
+/// Pools are kept for every pair, positions only for watched LP wallets and bin
+/// arrays only for pools those positions are in, to keep memory bounded.
+#[derive(Default)]
+pub struct PoolStateCache {
+    pools: RwLock<HashMap<Pubkey, Versioned<LbPair>>>,
+    bin_arrays: RwLock<HashMap<(Pubkey, i64), Versioned<BinArray>>>,
+    positions: RwLock<HashMap<Pubkey, Versioned<PositionV2>>>,
+}
+
+impl PoolStateCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Latest state of the `lb_pair` account
+    pub fn pool(&self, lb_pair: &Pubkey) -> Option<LbPair> {
+        self.pools
+            .read()
+            .unwrap()
+            .get(lb_pair)
+            .map(|pool| pool.value.clone())
+    }
+
+    /// Bin currently active in `lb_pair`, if its bin array has been streamed
+    pub fn active_bin(&self, lb_pair: &Pubkey) -> Option<Bin> {
+        let active_id = self.pools.read().unwrap().get(lb_pair)?.value.active_id;
+        let index = bin_array_index(active_id);
+        let offset = (active_id - index as i32 * MAX_BIN_PER_ARRAY) as usize;
+        self.bin_arrays
+            .read()
+            .unwrap()
+            .get(&(*lb_pair, index))
+            .and_then(|bin_array| bin_array.value.bins.get(offset).cloned())
+    }
+
+    /// Active bin, reserves and fee parameters of `lb_pair`
+    pub fn summary(&self, lb_pair: &Pubkey) -> Option<PoolSummary> {
+        let pool = self.pool(lb_pair)?;
+        Some(PoolSummary {
+            active_id: pool.active_id,
+            bin_step: pool.bin_step,
+            base_fee_bps: base_fee_bps(
+                pool.parameters.base_factor,
+                pool.bin_step,
+                pool.parameters.base_fee_power_factor,
+            ),
+            protocol_share_bps: pool.parameters.protocol_share,
+            active_bin_amounts: self
+                .active_bin(lb_pair)
+                .map(|bin| (bin.amount_x, bin.amount_y)),
+        })
+    }
+
+    /// Bin range of a watched position
+    pub fn position_range(&self, position: &Pubkey) -> Option<(i32, i32)> {
+        self.positions
+            .read()
+            .unwrap()
+            .get(position)
+            .map(|position| (position.value.lower_bin_id, position.value.upper_bin_id))
+    }
+
+    /// Whether a watched LP wallet has a position in `lb_pair`
+    pub fn is_watched_pool(&self, lb_pair: &Pubkey) -> bool {
+        self.positions
+            .read()
+            .unwrap()
+            .values()
+            .any(|position| position.value.lb_pair == *lb_pair)
+    }
+
+    fn update<K: std::hash::Hash + Eq, T>(
+        map: &RwLock<HashMap<K, Versioned<T>>>,
+        key: K,
+        slot: u64,
+        value: T,
+    ) {
+        let mut map = map.write().unwrap();
+        // Notifications may race, never replace a newer state with an older one
+        if map.get(&key).is_some_and(|current| current.slot > slot) {
+            return;
+        }
+        map.insert(key, Versioned { slot, value });
+    }
+}
+
+/// Program subscriptions for the accounts `PoolStateCache` keeps: every `LbPair`, the
+/// `PositionV2` accounts of `wallets` and the `BinArray` accounts of `pools`, each
+/// filtered by discriminator and size rather than streaming the whole program
+pub fn subscription_filters(
+    wallets: &[Pubkey],
+    pools: &[Pubkey],
+    commitment: CommitmentConfig,
+) -> Vec<Filters> {
+    let account_type = |discriminator: &[u8], len: u64| {
+        vec![
+            RpcFilterType::DataSize(len),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, discriminator)),
+        ]
+    };
+    let with_key = |mut filters: Vec<RpcFilterType>, offset: usize, key: &Pubkey| {
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            offset,
+            key.as_ref(),
+        )));
+        filters
+    };
+
+    let mut subscriptions = vec![account_type(LbPair::DISCRIMINATOR, LB_PAIR_LEN)];
+    subscriptions.extend(wallets.iter().map(|wallet| {
+        with_key(
+            account_type(PositionV2::DISCRIMINATOR, POSITION_V2_LEN),
+            POSITION_OWNER_OFFSET,
+            wallet,
+        )
+    }));
+    subscriptions.extend(pools.iter().map(|pool| {
+        with_key(
+            account_type(BinArray::DISCRIMINATOR, BIN_ARRAY_LEN),
+            BIN_ARRAY_LB_PAIR_OFFSET,
+            pool,
+        )
+    }));
+
+    subscriptions
+        .into_iter()
+        .map(|filters| {
+            Filters::new(
+                METEORA_PROGRAM_ID,
+                Some(RpcProgramAccountsConfig {
+                    filters: Some(filters),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        commitment: Some(commitment),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+            )
+        })
+        .collect()
+}
+
+/// Processor keeping `PoolStateCache` in sync with the DLMM account stream
+pub struct MeteoraAccountProcessor {
+    cache: Arc<PoolStateCache>,
+}
+
+impl MeteoraAccountProcessor {
+    pub fn new(cache: Arc<PoolStateCache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[async_trait]
+impl Processor for MeteoraAccountProcessor {
+    type InputType = (AccountMetadata, DecodedAccount<MeteoraDlmmAccount>);
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (account_metadata, decoded_account) = data;
+        let pubkey = account_metadata.pubkey;
+        let slot = account_metadata.slot;
+
+        match decoded_account.data {
+            MeteoraDlmmAccount::LbPair(lb_pair) => {
+                debug!(
+                    "LbPair {} active_id {} at slot {}",
+                    pubkey, lb_pair.active_id, slot
+                );
+                PoolStateCache::update(&self.cache.pools, pubkey, slot, lb_pair);
+            }
+            MeteoraDlmmAccount::BinArray(bin_array) => {
+                if self.cache.is_watched_pool(&bin_array.lb_pair) {
+                    debug!(
+                        "BinArray {} index {} at slot {}",
+                        pubkey, bin_array.index, slot
+                    );
+                    let key = (bin_array.lb_pair, bin_array.index);
+                    PoolStateCache::update(&self.cache.bin_arrays, key, slot, bin_array);
+                }
+            }
+            MeteoraDlmmAccount::PositionV2(position) => {
+                let owner = position.owner.to_string();
+                if LP_WALLETS.iter().any(|wallet| wallet == &owner) {
+                    debug!("PositionV2 {} of {} at slot {}", pubkey, owner, slot);
+                    PoolStateCache::update(&self.cache.positions, pubkey, slot, position);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_bin_array_index() {
+    assert_eq!(bin_array_index(0), 0);
+    assert_eq!(bin_array_index(69), 0);
+    assert_eq!(bin_array_index(70), 1);
+    assert_eq!(bin_array_index(-1), -1);
+    assert_eq!(bin_array_index(-70), -1);
+    assert_eq!(bin_array_index(-71), -2);
+
+    // 0.25% base fee of a bin step 25 pool with base factor 10000
+    assert!((base_fee_bps(10_000, 25, 0) - 25.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_subscription_filters() {
+    let wallet = Pubkey::new_unique();
+    let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let subscriptions = subscription_filters(&[wallet], &pools, CommitmentConfig::confirmed());
+    // Pools, then one position subscription per wallet and one bin array one per pool
+    assert_eq!(subscriptions.len(), 4);
+    for subscription in &subscriptions {
+        assert_eq!(subscription.pubkey, METEORA_PROGRAM_ID);
+        let filters = subscription
+            .program_subscribe_config
+            .as_ref()
+            .and_then(|config| config.filters.as_ref())
+            .unwrap();
+        assert!(
+            filters
+                .iter()
+                .any(|filter| matches!(filter, RpcFilterType::DataSize(_)))
+        );
+    }
+    let position_filters = subscriptions[1]
+        .program_subscribe_config
+        .as_ref()
+        .and_then(|config| config.filters.as_ref())
+        .unwrap();
+    assert!(
+        position_filters.contains(&RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            POSITION_OWNER_OFFSET,
+            wallet.as_ref()
+        )))
+    );
+}
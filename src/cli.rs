@@ -0,0 +1,216 @@
+use anyhow::{Context, Result, anyhow, bail};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{path::PathBuf, str::FromStr};
+
+/// Mode the agent is started in
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Follow the chain tip and alert on watched LP activity
+    Live { cold_start: bool },
+    /// Replay a bounded historical range through the processor and exit
+    Backfill(BackfillArgs),
+    /// Replay recorded transaction fixtures through the processor and exit
+    Replay(ReplayArgs),
+    /// Capture live transactions into a fixture file
+    Record(RecordArgs),
+    /// Print the registered positions of a wallet with their lifecycle
+    Positions { owner: Pubkey },
+}
+
+/// Bounds of a historical backfill.
+///
+/// Signatures are walked from `before` (exclusive, chain tip if unset) back to
+/// `until` (exclusive) and/or `min_slot`, at least one lower bound is required.
+#[derive(Debug, Default, PartialEq)]
+pub struct BackfillArgs {
+    pub before: Option<Signature>,
+    pub until: Option<Signature>,
+    pub min_slot: Option<u64>,
+    pub max_slot: Option<u64>,
+    pub notify: bool,
+}
+
+/// Recorded fixture files to replay
+#[derive(Debug, Default, PartialEq)]
+pub struct ReplayArgs {
+    pub files: Vec<PathBuf>,
+    /// Sleep between transactions according to their block times
+    pub original_timing: bool,
+    pub notify: bool,
+}
+
+/// Where and what to record
+#[derive(Debug, PartialEq)]
+pub struct RecordArgs {
+    pub out: PathBuf,
+    /// Address whose transactions are recorded, the DLMM program if unset
+    pub address: Option<Pubkey>,
+    /// Stop after this many transactions, record until interrupted if unset
+    pub limit: Option<usize>,
+}
+
+const USAGE: &str = "Usage:
+  meteora-agent [--cold-start]
+  meteora-agent backfill [--before <SIGNATURE>] [--until <SIGNATURE>]
+                         [--min-slot <SLOT>] [--max-slot <SLOT>] [--notify]
+  meteora-agent replay <FILE>... [--original-timing] [--notify]
+  meteora-agent record --out <FILE> [--address <PUBKEY>] [--limit <COUNT>]
+  meteora-agent positions <WALLET>";
+
+/// Parse command line arguments, without the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        None => Ok(Command::Live { cold_start: false }),
+        Some("--cold-start") => Ok(Command::Live { cold_start: true }),
+        Some("backfill") => parse_backfill_args(args).map(Command::Backfill),
+        Some("replay") => parse_replay_args(args).map(Command::Replay),
+        Some("record") => parse_record_args(args).map(Command::Record),
+        Some("positions") => parse_positions_args(args),
+        Some(other) => bail!("Unknown argument `{}`\n{}", other, USAGE),
+    }
+}
+
+fn parse_backfill_args(mut args: impl Iterator<Item = String>) -> Result<BackfillArgs> {
+    let mut backfill = BackfillArgs::default();
+    while let Some(flag) = args.next() {
+        if flag == "--notify" {
+            backfill.notify = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for `{}`\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--before" => backfill.before = Some(parse_signature(&value)?),
+            "--until" => backfill.until = Some(parse_signature(&value)?),
+            "--min-slot" => backfill.min_slot = Some(parse_slot(&value)?),
+            "--max-slot" => backfill.max_slot = Some(parse_slot(&value)?),
+            _ => bail!("Unknown backfill argument `{}`\n{}", flag, USAGE),
+        }
+    }
+
+    if backfill.until.is_none() && backfill.min_slot.is_none() {
+        bail!(
+            "Backfill needs a lower bound, pass --until or --min-slot\n{}",
+            USAGE
+        );
+    }
+    if let (Some(min_slot), Some(max_slot)) = (backfill.min_slot, backfill.max_slot) {
+        if min_slot > max_slot {
+            bail!("--min-slot {} is above --max-slot {}", min_slot, max_slot);
+        }
+    }
+    Ok(backfill)
+}
+
+fn parse_replay_args(args: impl Iterator<Item = String>) -> Result<ReplayArgs> {
+    let mut replay = ReplayArgs::default();
+    for arg in args {
+        match arg.as_str() {
+            "--original-timing" => replay.original_timing = true,
+            "--notify" => replay.notify = true,
+            flag if flag.starts_with("--") => {
+                bail!("Unknown replay argument `{}`\n{}", flag, USAGE)
+            }
+            file => replay.files.push(PathBuf::from(file)),
+        }
+    }
+    if replay.files.is_empty() {
+        bail!("Replay needs at least one fixture file\n{}", USAGE);
+    }
+    Ok(replay)
+}
+
+fn parse_record_args(mut args: impl Iterator<Item = String>) -> Result<RecordArgs> {
+    let mut out = None;
+    let mut address = None;
+    let mut limit = None;
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for `{}`\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--out" => out = Some(PathBuf::from(value)),
+            "--address" => {
+                address = Some(
+                    Pubkey::from_str(&value)
+                        .with_context(|| format!("Invalid address `{}`", value))?,
+                )
+            }
+            "--limit" => {
+                limit = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("Invalid limit `{}`", value))?,
+                )
+            }
+            _ => bail!("Unknown record argument `{}`\n{}", flag, USAGE),
+        }
+    }
+    Ok(RecordArgs {
+        out: out.ok_or_else(|| anyhow!("Record needs --out <FILE>\n{}", USAGE))?,
+        address,
+        limit,
+    })
+}
+
+fn parse_positions_args(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    let (Some(owner), None) = (args.next(), args.next()) else {
+        bail!("Positions needs exactly one wallet address\n{}", USAGE);
+    };
+    let owner =
+        Pubkey::from_str(&owner).with_context(|| format!("Invalid wallet address `{}`", owner))?;
+    Ok(Command::Positions { owner })
+}
+
+fn parse_signature(value: &str) -> Result<Signature> {
+    Signature::from_str(value).with_context(|| format!("Invalid signature `{}`", value))
+}
+
+fn parse_slot(value: &str) -> Result<u64> {
+    value
+        .parse()
+        .with_context(|| format!("Invalid slot `{}`", value))
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+    assert_eq!(
+        parse_args(args("")).unwrap(),
+        Command::Live { cold_start: false }
+    );
+    assert_eq!(
+        parse_args(args("--cold-start")).unwrap(),
+        Command::Live { cold_start: true }
+    );
+    assert_eq!(
+        parse_args(args("backfill --min-slot 10 --max-slot 20 --notify")).unwrap(),
+        Command::Backfill(BackfillArgs {
+            min_slot: Some(10),
+            max_slot: Some(20),
+            notify: true,
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        parse_args(args("replay a.jsonl b.jsonl --original-timing")).unwrap(),
+        Command::Replay(ReplayArgs {
+            files: vec![PathBuf::from("a.jsonl"), PathBuf::from("b.jsonl")],
+            original_timing: true,
+            notify: false,
+        })
+    );
+    assert!(parse_args(args("record --limit 5")).is_err());
+    let owner = Pubkey::new_unique();
+    assert_eq!(
+        parse_args(args(&format!("positions {}", owner))).unwrap(),
+        Command::Positions { owner }
+    );
+    assert!(parse_args(args("positions")).is_err());
+    // A backfill without lower bound would never stop
+    assert!(parse_args(args("backfill --max-slot 20")).is_err());
+    assert!(parse_args(args("backfill --min-slot 30 --max-slot 20")).is_err());
+}
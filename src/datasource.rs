@@ -0,0 +1,834 @@
+use {
+    async_trait::async_trait,
+    carbon_core::{
+        datasource::{Datasource, TransactionUpdate, Update, UpdateType},
+        error::{CarbonResult, Error},
+        metrics::MetricsCollection,
+        transformers::transaction_metadata_from_original_meta,
+    },
+    futures::{StreamExt, stream},
+    log::{info, warn},
+    solana_client::{
+        client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient,
+        rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+        rpc_response::RpcConfirmedTransactionStatusWithSignature,
+    },
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature},
+    solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding},
+    std::{
+        collections::{HashSet, VecDeque},
+        str::FromStr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::sync::mpsc::Sender,
+    tokio_util::sync::CancellationToken,
+};
+
+use crate::{
+    cli::BackfillArgs,
+    rpc_pool::RpcPool,
+    state::{Checkpoint, StateStore},
+};
+
+/// Number of recently sent signatures remembered to deduplicate across addresses
+const RECENT_SIGNATURES_CAPACITY: usize = 10_000;
+
+/// Fetch a single transaction through the pool and convert it into a pipeline update.
+///
+/// Returns `Ok(None)` when the RPC node has no status meta for the transaction.
+pub async fn fetch_transaction_update(
+    rpc_pool: &RpcPool,
+    signature: Signature,
+    commitment: CommitmentConfig,
+) -> CarbonResult<Option<TransactionUpdate>> {
+    let encoded_transaction = rpc_pool
+        .call(|rpc_client| async move {
+            fetch_encoded_transaction(&rpc_client, signature, commitment).await
+        })
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to fetch transaction {}: {}", signature, e)))?;
+    transaction_update_from_encoded(encoded_transaction)
+}
+
+/// Fetch a single transaction in the `getTransaction` base64 encoding
+pub async fn fetch_encoded_transaction(
+    rpc_client: &RpcClient,
+    signature: Signature,
+    commitment: CommitmentConfig,
+) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+    rpc_client
+        .get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(commitment),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await
+}
+
+/// Convert a `getTransaction` result into a pipeline update.
+///
+/// Returns `Ok(None)` when the transaction has no status meta or is not binary encoded.
+pub fn transaction_update_from_encoded(
+    encoded_transaction: EncodedConfirmedTransactionWithStatusMeta,
+) -> CarbonResult<Option<TransactionUpdate>> {
+    let transaction = encoded_transaction.transaction;
+    let Some(versioned_transaction) = transaction.transaction.decode() else {
+        warn!("Failed to decode transaction, skipping");
+        return Ok(None);
+    };
+    let Some(signature) = versioned_transaction.signatures.first().copied() else {
+        warn!("Transaction without signature, skipping");
+        return Ok(None);
+    };
+    let Some(meta) = transaction.meta else {
+        warn!("Transaction {} has no status meta, skipping", signature);
+        return Ok(None);
+    };
+
+    Ok(Some(TransactionUpdate {
+        signature,
+        transaction: versioned_transaction,
+        meta: transaction_metadata_from_original_meta(meta)?,
+        is_vote: false,
+        slot: encoded_transaction.slot,
+        block_time: encoded_transaction.block_time,
+    }))
+}
+
+/// Where the crawlers read signatures and transactions from, the `RpcPool` outside tests
+#[async_trait]
+pub trait TransactionSource: Send + Sync {
+    /// One `getSignaturesForAddress` page, newest first
+    async fn signatures(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+        commitment: CommitmentConfig,
+    ) -> CarbonResult<Vec<RpcConfirmedTransactionStatusWithSignature>>;
+
+    /// One transaction as a pipeline update, `None` when it has no status meta
+    async fn transaction(
+        &self,
+        signature: Signature,
+        commitment: CommitmentConfig,
+    ) -> CarbonResult<Option<TransactionUpdate>>;
+}
+
+#[async_trait]
+impl TransactionSource for RpcPool {
+    async fn signatures(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+        commitment: CommitmentConfig,
+    ) -> CarbonResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.call(|rpc_client| async move {
+            rpc_client
+                .get_signatures_for_address_with_config(
+                    address,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(limit),
+                        commitment: Some(commitment),
+                    },
+                )
+                .await
+        })
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to fetch signatures: {}", e)))
+    }
+
+    async fn transaction(
+        &self,
+        signature: Signature,
+        commitment: CommitmentConfig,
+    ) -> CarbonResult<Option<TransactionUpdate>> {
+        fetch_transaction_update(self, signature, commitment).await
+    }
+}
+
+/// Statuses of transactions of `address` newer than the `until` checkpoint, oldest
+/// first, paging back as far as needed to reach it. Without a checkpoint only the
+/// newest page is taken.
+pub async fn fetch_new_signatures(
+    source: &impl TransactionSource,
+    address: &Pubkey,
+    until: Option<&Checkpoint>,
+    batch_limit: usize,
+    commitment: CommitmentConfig,
+) -> CarbonResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    let until_slot = until.map(|checkpoint| checkpoint.slot);
+    let until = until.and_then(Checkpoint::signature);
+    let mut statuses = Vec::new();
+    let mut before = None;
+    loop {
+        let batch = source
+            .signatures(address, before, until, batch_limit, commitment)
+            .await?;
+
+        let full_page = batch.len() == batch_limit;
+        before = batch
+            .last()
+            .and_then(|status| Signature::from_str(&status.signature).ok());
+        // Paging past the checkpoint slot means its transaction was dropped and
+        // `until` will never be met
+        let passed_checkpoint =
+            until_slot.is_some_and(|slot| batch.last().is_some_and(|status| status.slot < slot));
+        statuses.extend(
+            batch
+                .into_iter()
+                .filter(|status| until_slot.is_none_or(|slot| status.slot >= slot)),
+        );
+        if !full_page || until.is_none() || before.is_none() || passed_checkpoint {
+            break;
+        }
+    }
+    statuses.reverse();
+    Ok(statuses)
+}
+
+/// Datasource that replays a bounded range of an account's history and then stops.
+///
+/// Unlike `RpcTransactionCrawler` it never polls for new signatures: once the
+/// lower bound is reached the pipeline is shut down. History is walked back one
+/// page at a time and each page is sent as soon as it is fetched, in chain order,
+/// so the pages reach the processor from the newest to the oldest.
+pub struct BackfillCrawler {
+    rpc_pool: Arc<RpcPool>,
+    account: Pubkey,
+    range: BackfillArgs,
+    batch_limit: usize,
+    max_concurrent_requests: usize,
+    commitment: CommitmentConfig,
+}
+
+impl BackfillCrawler {
+    pub fn new(
+        rpc_pool: Arc<RpcPool>,
+        account: Pubkey,
+        range: BackfillArgs,
+        batch_limit: usize,
+        max_concurrent_requests: usize,
+        commitment: CommitmentConfig,
+    ) -> Self {
+        Self {
+            rpc_pool,
+            account,
+            range,
+            batch_limit,
+            max_concurrent_requests,
+            commitment,
+        }
+    }
+
+    /// Signatures of the successful transactions of a page inside the slot range,
+    /// newest first, and whether the page reached the lower bound.
+    fn page_signatures(
+        &self,
+        page: Vec<RpcConfirmedTransactionStatusWithSignature>,
+    ) -> (Vec<Signature>, bool) {
+        let mut signatures = Vec::with_capacity(page.len());
+        for status in page {
+            if self
+                .range
+                .min_slot
+                .is_some_and(|min_slot| status.slot < min_slot)
+            {
+                return (signatures, true);
+            }
+            if self
+                .range
+                .max_slot
+                .is_some_and(|max_slot| status.slot > max_slot)
+                || status.err.is_some()
+            {
+                continue;
+            }
+            if let Ok(signature) = Signature::from_str(&status.signature) {
+                signatures.push(signature);
+            }
+        }
+        (signatures, false)
+    }
+
+    /// Fetch the transactions of `signatures` and send them in the given order.
+    ///
+    /// Returns how many were sent, or `None` once the pipeline stopped taking them.
+    async fn send_transactions(
+        &self,
+        sender: &Sender<Update>,
+        cancellation_token: &CancellationToken,
+        signatures: impl Iterator<Item = Signature>,
+    ) -> Option<usize> {
+        let rpc_pool = &self.rpc_pool;
+        let mut updates = stream::iter(signatures)
+            .map(|signature| fetch_transaction_update(rpc_pool, signature, self.commitment))
+            .buffered(self.max_concurrent_requests.max(1));
+
+        let mut sent = 0;
+        while let Some(update) = updates.next().await {
+            if cancellation_token.is_cancelled() {
+                return None;
+            }
+            match update {
+                Ok(Some(update)) => {
+                    if sender
+                        .send(Update::Transaction(Box::new(update)))
+                        .await
+                        .is_err()
+                    {
+                        return None;
+                    }
+                    sent += 1;
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Backfill skipped a transaction: {}", e),
+            }
+        }
+        Some(sent)
+    }
+}
+
+#[async_trait]
+impl Datasource for BackfillCrawler {
+    async fn consume(
+        &self,
+        sender: &Sender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let mut before = self.range.before;
+        let (mut total, mut sent) = (0, 0);
+
+        while !cancellation_token.is_cancelled() {
+            let page = self
+                .rpc_pool
+                .call(|rpc_client| async move {
+                    rpc_client
+                        .get_signatures_for_address_with_config(
+                            &self.account,
+                            GetConfirmedSignaturesForAddress2Config {
+                                before,
+                                until: self.range.until,
+                                limit: Some(self.batch_limit),
+                                commitment: Some(self.commitment),
+                            },
+                        )
+                        .await
+                })
+                .await
+                .map_err(|e| Error::Custom(format!("Failed to fetch signatures: {}", e)))?;
+
+            // An empty page means `until` or the first transaction of the account was reached
+            let Some(last) = page.last() else { break };
+            before = Some(
+                Signature::from_str(&last.signature)
+                    .map_err(|e| Error::Custom(format!("Invalid signature: {}", e)))?,
+            );
+
+            let (signatures, reached_lower_bound) = self.page_signatures(page);
+            total += signatures.len();
+            let Some(page_sent) = self
+                .send_transactions(sender, &cancellation_token, signatures.into_iter().rev())
+                .await
+            else {
+                break;
+            };
+            sent += page_sent;
+            if reached_lower_bound {
+                break;
+            }
+            info!("Backfill sent {} of {} transactions so far", sent, total);
+        }
+
+        info!("Backfill finished, {} of {} transactions sent", sent, total);
+        // Nothing else will arrive, let the pipeline drain and stop
+        cancellation_token.cancel();
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+/// Live crawler of every transaction of a program, reading through an `RpcPool`
+/// so each request fails over to another endpoint when the current one fails.
+///
+/// Each poll sends the transactions newer than the last one sent oldest first, so
+/// the checkpoint the processor moves as it goes never gets ahead of a transaction
+/// still to be processed. A poll cut short by a failed fetch is picked up by the
+/// next one right after the last transaction sent, and a restart resumes from the
+/// checkpoint, so nothing is skipped either way.
+pub struct FailoverCrawler<S = RpcPool> {
+    source: Arc<S>,
+    state: Arc<StateStore>,
+    account: Pubkey,
+    batch_limit: usize,
+    polling_interval: Duration,
+    commitment: CommitmentConfig,
+    max_concurrent_requests: usize,
+}
+
+impl<S: TransactionSource> FailoverCrawler<S> {
+    pub fn new(
+        source: Arc<S>,
+        state: Arc<StateStore>,
+        account: Pubkey,
+        batch_limit: usize,
+        polling_interval: Duration,
+        commitment: CommitmentConfig,
+        max_concurrent_requests: usize,
+    ) -> Self {
+        Self {
+            source,
+            state,
+            account,
+            batch_limit,
+            polling_interval,
+            commitment,
+            max_concurrent_requests,
+        }
+    }
+
+    /// Send the transactions newer than `until`, oldest first, moving `until` to each
+    /// one sent. Stops at the first transaction that cannot be fetched.
+    async fn crawl_new(
+        &self,
+        sender: &Sender<Update>,
+        cancellation_token: &CancellationToken,
+        until: &mut Option<Checkpoint>,
+    ) -> CarbonResult<()> {
+        let statuses = fetch_new_signatures(
+            &*self.source,
+            &self.account,
+            until.as_ref(),
+            self.batch_limit,
+            self.commitment,
+        )
+        .await?;
+
+        let source = &self.source;
+        let mut updates = stream::iter(&statuses)
+            .map(|status| async move {
+                let signature = Signature::from_str(&status.signature).ok();
+                let update = match signature {
+                    Some(signature) if status.err.is_none() => {
+                        source.transaction(signature, self.commitment).await?
+                    }
+                    _ => None,
+                };
+                CarbonResult::Ok((status, update))
+            })
+            .buffered(self.max_concurrent_requests.max(1));
+
+        while let Some(result) = updates.next().await {
+            if cancellation_token.is_cancelled() {
+                break;
+            }
+            let (status, update) = result?;
+            if let Some(update) = update {
+                if sender
+                    .send(Update::Transaction(Box::new(update)))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            *until = Some(Checkpoint {
+                signature: status.signature.clone(),
+                slot: status.slot,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: TransactionSource> Datasource for FailoverCrawler<S> {
+    async fn consume(
+        &self,
+        sender: &Sender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let mut until = self.state.checkpoint();
+        info!("Crawling {} from {:?}", self.account, until);
+        while !cancellation_token.is_cancelled() && !sender.is_closed() {
+            if let Err(e) = self
+                .crawl_new(sender, &cancellation_token, &mut until)
+                .await
+            {
+                // Retried from the last transaction sent on the next poll
+                warn!("Crawling {} failed: {}", self.account, e);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.polling_interval) => {}
+                _ = cancellation_token.cancelled() => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+/// Address crawled by `AddressCrawler` with its own polling cadence
+#[derive(Debug, Clone)]
+pub struct CrawlTarget {
+    pub address: Pubkey,
+    pub polling_interval: Duration,
+}
+
+/// Bounded set of signatures already sent to the pipeline, or being fetched
+struct RecentSignatures {
+    order: VecDeque<Signature>,
+    set: HashSet<Signature>,
+    capacity: usize,
+}
+
+impl RecentSignatures {
+    fn new(capacity: usize) -> Self {
+        Self {
+            order: VecDeque::with_capacity(capacity),
+            set: HashSet::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns `false` if `signature` was already seen
+    fn insert(&mut self, signature: Signature) -> bool {
+        if !self.set.insert(signature) {
+            return false;
+        }
+        self.order.push_back(signature);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Forget `signature`, e.g. when its transaction could not be fetched
+    fn remove(&mut self, signature: &Signature) {
+        if self.set.remove(signature) {
+            self.order.retain(|recent| recent != signature);
+        }
+    }
+}
+
+/// Whether `program_id` appears among the static or loaded accounts of the transaction
+fn touches_program(update: &TransactionUpdate, program_id: &Pubkey) -> bool {
+    update
+        .transaction
+        .message
+        .static_account_keys()
+        .contains(program_id)
+        || update.meta.loaded_addresses.writable.contains(program_id)
+        || update.meta.loaded_addresses.readonly.contains(program_id)
+}
+
+/// Datasource crawling `getSignaturesForAddress` for a set of addresses in parallel
+/// instead of the whole program.
+///
+/// Transactions seen through several addresses are sent once, and only those
+/// touching `program_id` reach the pipeline. Each address keeps its own
+/// checkpoint under `<stream>:<address>` in the state store.
+pub struct AddressCrawler {
+    rpc_pool: Arc<RpcPool>,
+    state: Arc<StateStore>,
+    stream: &'static str,
+    targets: Vec<CrawlTarget>,
+    program_id: Pubkey,
+    batch_limit: usize,
+    commitment: CommitmentConfig,
+    recent_signatures: Mutex<RecentSignatures>,
+}
+
+impl AddressCrawler {
+    pub fn new(
+        rpc_pool: Arc<RpcPool>,
+        state: Arc<StateStore>,
+        stream: &'static str,
+        targets: Vec<CrawlTarget>,
+        program_id: Pubkey,
+        batch_limit: usize,
+        commitment: CommitmentConfig,
+    ) -> Self {
+        Self {
+            rpc_pool,
+            state,
+            stream,
+            targets,
+            program_id,
+            batch_limit,
+            commitment,
+            recent_signatures: Mutex::new(RecentSignatures::new(RECENT_SIGNATURES_CAPACITY)),
+        }
+    }
+
+    async fn crawl_target(
+        &self,
+        target: &CrawlTarget,
+        sender: &Sender<Update>,
+        cancellation_token: &CancellationToken,
+    ) {
+        let checkpoint_key = format!("{}:{}", self.stream, target.address);
+        let mut until = self.state.stream_checkpoint(&checkpoint_key);
+        info!(
+            "Crawling {} every {:?} from {:?}",
+            checkpoint_key, target.polling_interval, until
+        );
+
+        loop {
+            match fetch_new_signatures(
+                &*self.rpc_pool,
+                &target.address,
+                until.as_ref(),
+                self.batch_limit,
+                self.commitment,
+            )
+            .await
+            {
+                Ok(statuses) => {
+                    // Newest transaction handled, the checkpoint never moves past a failed fetch
+                    let mut handled = None;
+                    for status in &statuses {
+                        let Ok(signature) = Signature::from_str(&status.signature) else {
+                            handled = Some(status);
+                            continue;
+                        };
+                        // Claimed before the fetch, so another address crawling the same
+                        // transaction concurrently skips it
+                        if status.err.is_some()
+                            || !self.recent_signatures.lock().unwrap().insert(signature)
+                        {
+                            handled = Some(status);
+                            continue;
+                        }
+                        let update =
+                            fetch_transaction_update(&self.rpc_pool, signature, self.commitment)
+                                .await;
+                        match update {
+                            Ok(Some(update)) if touches_program(&update, &self.program_id) => {
+                                if sender
+                                    .send(Update::Transaction(Box::new(update)))
+                                    .await
+                                    .is_err()
+                                {
+                                    return;
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(e) => {
+                                // Retried from here on the next poll
+                                self.recent_signatures.lock().unwrap().remove(&signature);
+                                warn!(
+                                    "Failed to fetch transaction {} of {}: {}",
+                                    signature, checkpoint_key, e
+                                );
+                                break;
+                            }
+                        }
+                        handled = Some(status);
+                    }
+
+                    if let Some(newest) = handled {
+                        let checkpoint = Checkpoint {
+                            signature: newest.signature.clone(),
+                            slot: newest.slot,
+                        };
+                        if let Some(signature) = checkpoint.signature() {
+                            self.state.record_stream_checkpoint(
+                                &checkpoint_key,
+                                &signature,
+                                checkpoint.slot,
+                            );
+                            until = Some(checkpoint);
+                        }
+                    }
+                }
+                Err(e) => warn!("Crawling {} failed: {}", checkpoint_key, e),
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(target.polling_interval) => {}
+                _ = cancellation_token.cancelled() => return,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Datasource for AddressCrawler {
+    async fn consume(
+        &self,
+        sender: &Sender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let crawls = self
+            .targets
+            .iter()
+            .map(|target| self.crawl_target(target, sender, &cancellation_token));
+        futures::future::join_all(crawls).await;
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+#[test]
+fn test_recent_signatures() {
+    let mut recent = RecentSignatures::new(2);
+    let first = Signature::new_unique();
+    assert!(recent.insert(first));
+    assert!(!recent.insert(first));
+    recent.insert(Signature::new_unique());
+    recent.insert(Signature::new_unique());
+    // The oldest signature was evicted once the capacity was exceeded
+    assert!(recent.insert(first));
+    // A removed signature can be claimed again
+    recent.remove(&first);
+    assert!(recent.insert(first));
+    assert_eq!(recent.order.len(), recent.set.len());
+}
+
+#[tokio::test]
+async fn test_failover_mid_batch() {
+    use {
+        solana_sdk::transaction::VersionedTransaction,
+        solana_transaction_status::TransactionStatusMeta,
+    };
+
+    /// Program history, newest first, whose transaction fetches fail once for `failing`
+    struct StubSource {
+        history: Vec<RpcConfirmedTransactionStatusWithSignature>,
+        failing: Mutex<Option<Signature>>,
+    }
+
+    #[async_trait]
+    impl TransactionSource for StubSource {
+        async fn signatures(
+            &self,
+            _address: &Pubkey,
+            before: Option<Signature>,
+            until: Option<Signature>,
+            limit: usize,
+            _commitment: CommitmentConfig,
+        ) -> CarbonResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+            let position = |signature: Option<Signature>| {
+                signature.and_then(|signature| {
+                    self.history
+                        .iter()
+                        .position(|status| status.signature == signature.to_string())
+                })
+            };
+            let start = position(before).map_or(0, |index| index + 1);
+            let end = position(until).unwrap_or(self.history.len());
+            Ok(self.history[start..end.max(start)]
+                .iter()
+                .take(limit)
+                .cloned()
+                .collect())
+        }
+
+        async fn transaction(
+            &self,
+            signature: Signature,
+            _commitment: CommitmentConfig,
+        ) -> CarbonResult<Option<TransactionUpdate>> {
+            let mut failing = self.failing.lock().unwrap();
+            if *failing == Some(signature) {
+                *failing = None;
+                return Err(Error::Custom("endpoint went down".to_string()));
+            }
+            Ok(Some(TransactionUpdate {
+                signature,
+                transaction: VersionedTransaction::default(),
+                meta: TransactionStatusMeta::default(),
+                is_vote: false,
+                slot: 0,
+                block_time: None,
+            }))
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "meteora-agent-state-failover-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let state = Arc::new(StateStore::open(&path).unwrap());
+    let signatures: Vec<Signature> = (0..5).map(|_| Signature::new_unique()).collect();
+    state.record_processed(&signatures[0], 100);
+
+    let history = signatures
+        .iter()
+        .enumerate()
+        .rev()
+        .map(
+            |(index, signature)| RpcConfirmedTransactionStatusWithSignature {
+                signature: signature.to_string(),
+                slot: 100 + index as u64,
+                err: None,
+                memo: None,
+                block_time: None,
+                confirmation_status: None,
+            },
+        )
+        .collect();
+    let source = Arc::new(StubSource {
+        history,
+        failing: Mutex::new(Some(signatures[3])),
+    });
+    let crawler = FailoverCrawler::new(
+        source,
+        state.clone(),
+        Pubkey::new_unique(),
+        2,
+        Duration::from_secs(1),
+        CommitmentConfig::confirmed(),
+        1,
+    );
+
+    // The batch is sent oldest first and cut short when the fetch fails, the
+    // next poll carries on right after the last transaction sent
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+    let cancellation_token = CancellationToken::new();
+    let mut until = state.checkpoint();
+    assert!(
+        crawler
+            .crawl_new(&sender, &cancellation_token, &mut until)
+            .await
+            .is_err()
+    );
+    assert_eq!(until.as_ref().map(|checkpoint| checkpoint.slot), Some(102));
+    crawler
+        .crawl_new(&sender, &cancellation_token, &mut until)
+        .await
+        .unwrap();
+    drop(sender);
+
+    let mut sent = Vec::new();
+    while let Some(Update::Transaction(update)) = receiver.recv().await {
+        sent.push(update.signature);
+    }
+    assert_eq!(sent, signatures[1..]);
+    let _ = std::fs::remove_file(&path);
+}
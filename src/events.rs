@@ -0,0 +1,914 @@
+use {
+    carbon_core::instruction::InstructionMetadata,
+    carbon_meteora_dlmm_decoder::PROGRAM_ID as METEORA_PROGRAM_ID,
+    serde::Serialize,
+    solana_sdk::{pubkey, pubkey::Pubkey},
+    std::{fmt, str::FromStr},
+};
+
+use crate::{
+    account_processer::PoolSummary,
+    records::{
+        AdminChange, BinRemoval, Claimed, LiquidityAdded, LiquidityRemoved, PoolCreated,
+        PositionOpened, SwapExecuted,
+    },
+    state::{HarvestTotals, PositionEvent, PositionRecord},
+    token::{MintInfo, TokenProfile, is_familiar},
+    token_registry::TokenRegistry,
+};
+
+/// Programs commonly routing users into DLMM through CPI
+const KNOWN_ROUTES: &[(Pubkey, &str)] = &[
+    (
+        pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4"),
+        "Jupiter v6",
+    ),
+    (
+        pubkey!("DCA265Vj8a9CEuX1eb1LWRnDT7uK6q1xMipnNyatn23M"),
+        "Jupiter DCA",
+    ),
+    (
+        pubkey!("jupoNjAxXgZ4rjzxzPMP4oxduvQsQtZzyknqvzYNrNu"),
+        "Jupiter Limit Order",
+    ),
+    (
+        pubkey!("24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi"),
+        "Meteora Vault",
+    ),
+    (
+        pubkey!("6LtLpnUFNByNXLyCoK9wA2MykKAmQNZKBdY8s47dehDc"),
+        "Kamino Liquidity",
+    ),
+];
+
+/// Name of a program invoking DLMM, its address when it is not a known route
+fn route_name(program_id: &Pubkey) -> String {
+    KNOWN_ROUTES
+        .iter()
+        .find(|(known, _)| known == program_id)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| program_id.to_string())
+}
+
+/// Where an event happened on chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventContext {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// Index of the top-level instruction the event comes from
+    pub instruction_index: u32,
+    /// 1 for top-level instructions, higher when reached through CPI
+    pub stack_height: u32,
+    /// End user, the fee payer signing the transaction
+    pub signer: String,
+    /// Top-level program that invoked DLMM through CPI, `None` for direct calls
+    pub invoked_by: Option<String>,
+    /// Name of `invoked_by` when it is a known aggregator or vault program
+    pub route: Option<String>,
+}
+
+impl EventContext {
+    pub fn new(instruction_metadata: &InstructionMetadata) -> Self {
+        let transaction_metadata = &instruction_metadata.transaction_metadata;
+        // Program ids of top-level instructions are always static account keys. Events
+        // DLMM emits through a self-CPI belong to a direct call, not to a route.
+        let message = &transaction_metadata.message;
+        let invoked_by = (instruction_metadata.stack_height > 1)
+            .then(|| {
+                let instruction = message
+                    .instructions()
+                    .get(instruction_metadata.index as usize)?;
+                message
+                    .static_account_keys()
+                    .get(instruction.program_id_index as usize)
+                    .copied()
+            })
+            .flatten()
+            .filter(|program_id| *program_id != METEORA_PROGRAM_ID);
+        Self {
+            signature: transaction_metadata.signature.to_string(),
+            slot: transaction_metadata.slot,
+            block_time: transaction_metadata.block_time,
+            instruction_index: instruction_metadata.index,
+            stack_height: instruction_metadata.stack_height,
+            signer: transaction_metadata.fee_payer.to_string(),
+            invoked_by: invoked_by.map(|program_id| program_id.to_string()),
+            route: invoked_by.map(|program_id| route_name(&program_id)),
+        }
+    }
+
+    /// ` via <route>` for events reached through CPI, empty otherwise
+    pub fn via(&self) -> String {
+        match &self.route {
+            Some(route) => format!(" via {}", route),
+            None => String::new(),
+        }
+    }
+}
+
+/// Mainnet epoch length, to pick the transfer fee in force at a slot
+const SLOTS_PER_EPOCH: u64 = 432_000;
+
+/// Token mint with its symbol, decimals, amount-changing extensions and risk profile,
+/// when they could be fetched
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Token {
+    pub mint: String,
+    pub symbol: Option<String>,
+    #[serde(flatten)]
+    pub info: Option<MintInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<TokenProfile>,
+}
+
+impl Token {
+    /// Look up the symbol and mint account of `mint` through the token registry
+    pub async fn resolve(mint: &Pubkey) -> Self {
+        let entry = TokenRegistry::global().get(mint).await;
+        Self {
+            mint: mint.to_string(),
+            symbol: entry.symbol().map(str::to_string),
+            info: entry.mint_info,
+            profile: entry.profile,
+        }
+    }
+
+    /// Risk flags worth an alert: none for a well-known or listed token
+    pub fn risk_flags(&self) -> Vec<String> {
+        let familiar = Pubkey::from_str(&self.mint).is_ok_and(|mint| is_familiar(&mint));
+        match &self.profile {
+            Some(profile) if !familiar => profile.risk_flags(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn decimals(&self) -> Option<u8> {
+        self.info.map(|info| info.decimals)
+    }
+
+    /// Amount of this token moved at `slot`, with the Token-2022 transfer fee withheld
+    /// and the interest accrued at `block_time`
+    pub fn amount(&self, raw: u64, slot: u64, block_time: Option<i64>) -> TokenAmount {
+        let transfer_fee = self.info.and_then(|info| {
+            let transfer_fee = info.transfer_fee?;
+            Some(transfer_fee.fee(slot / SLOTS_PER_EPOCH, raw))
+        });
+        let ui_amount = self.info.map(|info| {
+            let scale = match (info.interest_bearing, block_time) {
+                (Some(interest_bearing), Some(block_time)) => interest_bearing.scale(block_time),
+                (Some(interest_bearing), None) => {
+                    interest_bearing.scale(interest_bearing.last_update_timestamp)
+                }
+                (None, _) => 1.0,
+            };
+            raw as f64 * scale / 10f64.powi(info.decimals as i32)
+        });
+        TokenAmount {
+            token: self.clone(),
+            raw,
+            transfer_fee,
+            ui_amount,
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{}", symbol),
+            None => write!(f, "{}", short_mint(&self.mint)),
+        }
+    }
+}
+
+/// Label of a mint without a known symbol, e.g. `EPjF…Dt1v`
+fn short_mint(mint: &str) -> String {
+    match (mint.get(..4), mint.get(mint.len().saturating_sub(4)..)) {
+        (Some(head), Some(tail)) if mint.len() > 8 => format!("{}…{}", head, tail),
+        _ => mint.to_string(),
+    }
+}
+
+/// Amount of a token, in base units and as a UI amount when the mint is known
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenAmount {
+    #[serde(flatten)]
+    pub token: Token,
+    /// Amount in base units
+    pub raw: u64,
+    /// Part of `raw` withheld by a Token-2022 transfer fee, in base units
+    pub transfer_fee: Option<u64>,
+    /// Amount adjusted for decimals and Token-2022 interest
+    pub ui_amount: Option<f64>,
+}
+
+impl TokenAmount {
+    pub async fn resolve(mint: &Pubkey, raw: u64, context: &EventContext) -> Self {
+        Token::resolve(mint)
+            .await
+            .amount(raw, context.slot, context.block_time)
+    }
+
+    /// Amount moved within the pool, no transfer fee applies
+    pub fn without_transfer_fee(self) -> Self {
+        Self {
+            transfer_fee: None,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ui_amount {
+            Some(ui_amount) => write!(f, "{} {}", ui_amount, self.token)?,
+            None => write!(f, "{} base units of {}", self.raw, self.token)?,
+        }
+        match (self.transfer_fee, self.ui_amount) {
+            (Some(fee), Some(ui_amount)) if fee > 0 => write!(
+                f,
+                " ({} transfer fee)",
+                ui_amount * fee as f64 / self.raw as f64
+            ),
+            (Some(fee), None) if fee > 0 => write!(f, " ({} transfer fee)", fee),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Amount of `mint`, if the mint is known
+async fn optional_amount(
+    mint: Option<Pubkey>,
+    raw: u64,
+    context: &EventContext,
+) -> Option<TokenAmount> {
+    match mint {
+        Some(mint) => Some(TokenAmount::resolve(&mint, raw, context).await),
+        None => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidityAddedEvent {
+    pub context: EventContext,
+    pub instruction: String,
+    pub wallet: String,
+    pub pool: String,
+    pub position: String,
+    /// `None` for one-sided deposits of the other token
+    pub amount_x: Option<TokenAmount>,
+    pub amount_y: Option<TokenAmount>,
+    pub lower_bin_id: i32,
+    pub upper_bin_id: i32,
+    pub active_id: Option<i32>,
+    pub strategy: String,
+}
+
+impl LiquidityAddedEvent {
+    pub async fn new(context: EventContext, record: &LiquidityAdded) -> Self {
+        let (amount_x, amount_y) = tokio::join!(
+            optional_amount(record.token_x_mint, record.amount_x, &context),
+            optional_amount(record.token_y_mint, record.amount_y, &context)
+        );
+        Self {
+            context,
+            instruction: record.instruction.to_string(),
+            wallet: record.sender.to_string(),
+            pool: record.lb_pair.to_string(),
+            position: record.position.to_string(),
+            amount_x,
+            amount_y,
+            lower_bin_id: record.lower_bin_id,
+            upper_bin_id: record.upper_bin_id,
+            active_id: record.active_id,
+            strategy: record.strategy.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidityRemovedEvent {
+    pub context: EventContext,
+    pub instruction: String,
+    pub wallet: String,
+    pub pool: String,
+    pub position: String,
+    /// Amounts withdrawn, `None` for `ClosePosition`
+    pub amount_x: Option<TokenAmount>,
+    pub amount_y: Option<TokenAmount>,
+    pub lower_bin_id: Option<i32>,
+    pub upper_bin_id: Option<i32>,
+    pub bins: Vec<BinRemoval>,
+    pub fully_exited: bool,
+}
+
+impl LiquidityRemovedEvent {
+    pub async fn new(context: EventContext, record: &LiquidityRemoved) -> Self {
+        let (amount_x, amount_y) = tokio::join!(
+            optional_amount(record.token_x_mint, record.amount_x, &context),
+            optional_amount(record.token_y_mint, record.amount_y, &context)
+        );
+        Self {
+            context,
+            instruction: record.instruction.to_string(),
+            wallet: record.sender.to_string(),
+            pool: record.lb_pair.to_string(),
+            position: record.position.to_string(),
+            amount_x,
+            amount_y,
+            lower_bin_id: record.lower_bin_id,
+            upper_bin_id: record.upper_bin_id,
+            bins: record.bins.clone(),
+            fully_exited: record.fully_exited,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapExecutedEvent {
+    pub context: EventContext,
+    pub instruction: String,
+    pub wallet: String,
+    pub pool: String,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    /// Fees are paid in the input token
+    pub fee: Option<TokenAmount>,
+    pub protocol_fee: Option<TokenAmount>,
+    pub start_bin_id: Option<i32>,
+    pub end_bin_id: Option<i32>,
+    /// Pool state streamed from the program accounts, if enabled
+    pub pool_state: Option<PoolSummary>,
+}
+
+impl SwapExecutedEvent {
+    pub async fn new(
+        context: EventContext,
+        record: &SwapExecuted,
+        pool_state: Option<PoolSummary>,
+    ) -> Self {
+        let (mint_in, mint_out) = record.mints_in_out();
+        let (token_in, token_out) =
+            tokio::join!(Token::resolve(&mint_in), Token::resolve(&mint_out));
+        let (slot, block_time) = (context.slot, context.block_time);
+        Self {
+            context,
+            instruction: record.instruction.to_string(),
+            wallet: record.user.to_string(),
+            pool: record.lb_pair.to_string(),
+            amount_in: token_in.amount(record.amount_in, slot, block_time),
+            amount_out: token_out.amount(record.amount_out, slot, block_time),
+            fee: record.fee.map(|fee| {
+                token_in
+                    .amount(fee, slot, block_time)
+                    .without_transfer_fee()
+            }),
+            protocol_fee: record.protocol_fee.map(|fee| {
+                token_in
+                    .amount(fee, slot, block_time)
+                    .without_transfer_fee()
+            }),
+            start_bin_id: record.start_bin_id,
+            end_bin_id: record.end_bin_id,
+            pool_state,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClaimEvent {
+    pub context: EventContext,
+    pub instruction: String,
+    pub wallet: String,
+    pub pool: String,
+    pub position: String,
+    /// Reward slot of the pool, `None` for swap fees
+    pub reward_index: Option<u64>,
+    pub amounts: Vec<TokenAmount>,
+    /// Running totals, for watched LP wallets
+    pub position_harvest: Option<HarvestTotals>,
+    pub wallet_harvest: Option<HarvestTotals>,
+}
+
+impl ClaimEvent {
+    pub async fn new(
+        context: EventContext,
+        record: &Claimed,
+        harvests: Option<(HarvestTotals, HarvestTotals)>,
+    ) -> Self {
+        let amounts = futures::future::join_all(
+            record
+                .amounts
+                .iter()
+                .map(|(mint, raw)| TokenAmount::resolve(mint, *raw, &context)),
+        )
+        .await;
+        let (position_harvest, wallet_harvest) = harvests.unzip();
+        Self {
+            context,
+            instruction: record.instruction.to_string(),
+            wallet: record.owner.to_string(),
+            pool: record.lb_pair.to_string(),
+            position: record.position.to_string(),
+            reward_index: record.reward_index,
+            amounts,
+            position_harvest,
+            wallet_harvest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionOpenedEvent {
+    pub context: EventContext,
+    pub wallet: String,
+    pub pool: String,
+    pub position: String,
+    pub lower_bin_id: Option<i32>,
+    pub upper_bin_id: Option<i32>,
+}
+
+impl PositionOpenedEvent {
+    pub fn new(context: EventContext, record: &PositionOpened) -> Self {
+        Self {
+            context,
+            wallet: record.owner.to_string(),
+            pool: record.lb_pair.to_string(),
+            position: record.position.to_string(),
+            lower_bin_id: record.bin_range.map(|(lower, _)| lower),
+            upper_bin_id: record.bin_range.map(|(_, upper)| upper),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionClosedEvent {
+    pub context: EventContext,
+    pub wallet: String,
+    pub pool: String,
+    pub position: String,
+    pub adds: usize,
+    pub removes: usize,
+    pub claims: usize,
+    /// Time between open and close, when both block times are known
+    pub open_seconds: Option<i64>,
+}
+
+impl PositionClosedEvent {
+    pub fn new(context: EventContext, position: &Pubkey, record: &PositionRecord) -> Self {
+        let count = |matches: fn(&PositionEvent) -> bool| {
+            record
+                .activity
+                .iter()
+                .filter(|activity| matches(&activity.event))
+                .count()
+        };
+        let opened_at = record.opened().and_then(|activity| activity.block_time);
+        let closed_at = record.closed().and_then(|activity| activity.block_time);
+        Self {
+            context,
+            wallet: record.owner.clone(),
+            pool: record.lb_pair.clone(),
+            position: position.to_string(),
+            adds: count(|event| matches!(event, PositionEvent::Added { .. })),
+            removes: count(|event| matches!(event, PositionEvent::Removed { .. })),
+            claims: count(|event| matches!(event, PositionEvent::Claimed { .. })),
+            open_seconds: opened_at
+                .zip(closed_at)
+                .map(|(opened_at, closed_at)| closed_at - opened_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolCreatedEvent {
+    pub context: EventContext,
+    pub instruction: String,
+    pub pool: String,
+    pub token_x: Token,
+    pub token_y: Token,
+    pub bin_step: u16,
+    pub base_fee_bps: Option<f64>,
+    pub activation_point: Option<u64>,
+    /// Price of token X in token Y, adjusted for decimals when they are known
+    pub initial_price: Option<f64>,
+}
+
+impl PoolCreatedEvent {
+    pub async fn new(
+        context: EventContext,
+        record: &PoolCreated,
+        base_fee_bps: Option<f64>,
+    ) -> Self {
+        let (token_x, token_y) = tokio::join!(
+            Token::resolve(&record.token_x_mint),
+            Token::resolve(&record.token_y_mint)
+        );
+        let initial_price =
+            record
+                .initial_price()
+                .map(|price| match (token_x.decimals(), token_y.decimals()) {
+                    (Some(decimals_x), Some(decimals_y)) => {
+                        price * 10f64.powi(decimals_x as i32 - decimals_y as i32)
+                    }
+                    _ => price,
+                });
+        Self {
+            context,
+            instruction: record.instruction.to_string(),
+            pool: record.lb_pair.to_string(),
+            token_x,
+            token_y,
+            bin_step: record.bin_step,
+            base_fee_bps,
+            activation_point: record.activation_point,
+            initial_price,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AdminChangeEvent {
+    pub context: EventContext,
+    pub instruction: String,
+    pub pool: String,
+    /// Instruction or event arguments, as JSON
+    pub details: String,
+    pub pool_state: Option<PoolSummary>,
+    /// Whether a watched LP wallet has a position in the pool
+    pub watched: bool,
+}
+
+impl AdminChangeEvent {
+    pub fn new(
+        context: EventContext,
+        record: &AdminChange,
+        pool_state: Option<PoolSummary>,
+        watched: bool,
+    ) -> Self {
+        Self {
+            context,
+            instruction: record.instruction.to_string(),
+            pool: record.lb_pair.to_string(),
+            details: record.details.clone(),
+            pool_state,
+            watched,
+        }
+    }
+}
+
+/// DLMM activity in a form independent of the decoder types, consumed by the event sinks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MeteoraEvent {
+    LiquidityAdded(LiquidityAddedEvent),
+    LiquidityRemoved(LiquidityRemovedEvent),
+    Swap(SwapExecutedEvent),
+    Claim(ClaimEvent),
+    PositionOpened(PositionOpenedEvent),
+    PositionClosed(PositionClosedEvent),
+    PoolCreated(PoolCreatedEvent),
+    AdminChange(AdminChangeEvent),
+}
+
+impl MeteoraEvent {
+    pub fn context(&self) -> &EventContext {
+        match self {
+            MeteoraEvent::LiquidityAdded(event) => &event.context,
+            MeteoraEvent::LiquidityRemoved(event) => &event.context,
+            MeteoraEvent::Swap(event) => &event.context,
+            MeteoraEvent::Claim(event) => &event.context,
+            MeteoraEvent::PositionOpened(event) => &event.context,
+            MeteoraEvent::PositionClosed(event) => &event.context,
+            MeteoraEvent::PoolCreated(event) => &event.context,
+            MeteoraEvent::AdminChange(event) => &event.context,
+        }
+    }
+
+    /// Wallet behind the event, if any, and the pool it happened in
+    pub fn wallet_and_pool(&self) -> (Option<&String>, &String) {
+        match self {
+            MeteoraEvent::LiquidityAdded(event) => (Some(&event.wallet), &event.pool),
+            MeteoraEvent::LiquidityRemoved(event) => (Some(&event.wallet), &event.pool),
+            MeteoraEvent::Swap(event) => (Some(&event.wallet), &event.pool),
+            MeteoraEvent::Claim(event) => (Some(&event.wallet), &event.pool),
+            MeteoraEvent::PositionOpened(event) => (Some(&event.wallet), &event.pool),
+            MeteoraEvent::PositionClosed(event) => (Some(&event.wallet), &event.pool),
+            MeteoraEvent::PoolCreated(event) => (None, &event.pool),
+            MeteoraEvent::AdminChange(event) => (None, &event.pool),
+        }
+    }
+
+    /// Tokens moved or listed by the event, each once
+    pub fn tokens(&self) -> Vec<&Token> {
+        let amounts: Vec<&TokenAmount> = match self {
+            MeteoraEvent::LiquidityAdded(event) => {
+                event.amount_x.iter().chain(&event.amount_y).collect()
+            }
+            MeteoraEvent::LiquidityRemoved(event) => {
+                event.amount_x.iter().chain(&event.amount_y).collect()
+            }
+            MeteoraEvent::Swap(event) => vec![&event.amount_in, &event.amount_out],
+            MeteoraEvent::Claim(event) => event.amounts.iter().collect(),
+            MeteoraEvent::PoolCreated(event) => return vec![&event.token_x, &event.token_y],
+            _ => Vec::new(),
+        };
+        let mut tokens: Vec<&Token> = Vec::new();
+        for amount in amounts {
+            if !tokens.iter().any(|token| token.mint == amount.token.mint) {
+                tokens.push(&amount.token);
+            }
+        }
+        tokens
+    }
+}
+
+/// What a transaction did overall, from the DLMM activity it contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    /// Liquidity withdrawn and added back, usually around a new active bin
+    Rebalance,
+    /// Liquidity added or a position opened
+    Entry,
+    /// Liquidity withdrawn or a position closed
+    Exit,
+    /// Fees or rewards claimed only
+    Harvest,
+    /// Swaps only
+    Swap,
+}
+
+impl TransactionKind {
+    /// Classify a transaction from whether it added, removed or claimed liquidity or swapped
+    pub fn from_activity(added: bool, removed: bool, claimed: bool, swapped: bool) -> Option<Self> {
+        match (added, removed) {
+            (true, true) => Some(TransactionKind::Rebalance),
+            (true, false) => Some(TransactionKind::Entry),
+            (false, true) => Some(TransactionKind::Exit),
+            (false, false) if claimed => Some(TransactionKind::Harvest),
+            (false, false) if swapped => Some(TransactionKind::Swap),
+            (false, false) => None,
+        }
+    }
+
+    /// Classify a transaction from its events, `None` without any LP or swap activity
+    pub fn classify(events: &[MeteoraEvent]) -> Option<Self> {
+        let any = |matches: fn(&MeteoraEvent) -> bool| events.iter().any(matches);
+        Self::from_activity(
+            any(|event| {
+                matches!(
+                    event,
+                    MeteoraEvent::LiquidityAdded(_) | MeteoraEvent::PositionOpened(_)
+                )
+            }),
+            any(|event| {
+                matches!(
+                    event,
+                    MeteoraEvent::LiquidityRemoved(_) | MeteoraEvent::PositionClosed(_)
+                )
+            }),
+            any(|event| matches!(event, MeteoraEvent::Claim(_))),
+            any(|event| matches!(event, MeteoraEvent::Swap(_))),
+        )
+    }
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionKind::Rebalance => write!(f, "Rebalance"),
+            TransactionKind::Entry => write!(f, "Entry"),
+            TransactionKind::Exit => write!(f, "Exit"),
+            TransactionKind::Harvest => write!(f, "Harvest"),
+            TransactionKind::Swap => write!(f, "Swap"),
+        }
+    }
+}
+
+/// Every event of one transaction, reported together once the transaction is processed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionReport {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: TransactionKind,
+    /// End user signing the transaction
+    pub signer: String,
+    /// Programs routing into DLMM through CPI, empty for direct calls
+    pub routes: Vec<String>,
+    /// Wallets and pools involved, in order of appearance
+    pub wallets: Vec<String>,
+    pub pools: Vec<String>,
+    pub events: Vec<MeteoraEvent>,
+}
+
+impl TransactionReport {
+    /// Report of the events of one transaction, `None` without any LP or swap activity
+    pub fn new(events: Vec<MeteoraEvent>) -> Option<Self> {
+        let kind = TransactionKind::classify(&events)?;
+        let context = events.first()?.context().clone();
+        let mut routes = Vec::new();
+        let mut wallets = Vec::new();
+        let mut pools = Vec::new();
+        for event in &events {
+            if let Some(route) = event
+                .context()
+                .route
+                .as_ref()
+                .filter(|route| !routes.contains(*route))
+            {
+                routes.push(route.clone());
+            }
+            let (wallet, pool) = event.wallet_and_pool();
+            if let Some(wallet) = wallet.filter(|wallet| !wallets.contains(wallet)) {
+                wallets.push(wallet.clone());
+            }
+            if !pools.contains(pool) {
+                pools.push(pool.clone());
+            }
+        }
+        Some(Self {
+            signature: context.signature,
+            slot: context.slot,
+            block_time: context.block_time,
+            kind,
+            signer: context.signer,
+            routes,
+            wallets,
+            pools,
+            events,
+        })
+    }
+
+    /// ` via <routes>` when DLMM was reached through CPI, empty otherwise
+    pub fn via(&self) -> String {
+        if self.routes.is_empty() {
+            return String::new();
+        }
+        format!(" via {}", self.routes.join(", "))
+    }
+}
+
+#[test]
+fn test_token_amount() {
+    use crate::token::{InterestBearingConfig, TokenProgram, TransferFee, TransferFeeConfig};
+
+    let mint_info = MintInfo {
+        program: TokenProgram::Token,
+        decimals: 6,
+        transfer_fee: None,
+        interest_bearing: None,
+    };
+    let token = Token {
+        mint: Pubkey::new_unique().to_string(),
+        symbol: Some("USDC".to_string()),
+        info: Some(mint_info),
+        profile: None,
+    };
+    assert_eq!(token.amount(1_500_000, 0, None).to_string(), "1.5 USDC");
+
+    // 1% transfer fee
+    let transfer_fee = TransferFee {
+        epoch: 0,
+        maximum_fee: u64::MAX,
+        basis_points: 100,
+    };
+    let with_fee = Token {
+        info: Some(MintInfo {
+            program: TokenProgram::Token2022,
+            transfer_fee: Some(TransferFeeConfig {
+                older: transfer_fee,
+                newer: transfer_fee,
+            }),
+            ..mint_info
+        }),
+        ..token.clone()
+    };
+    let amount = with_fee.amount(2_000_000, 0, None);
+    assert_eq!(amount.transfer_fee, Some(20_000));
+    assert_eq!(amount.to_string(), "2 USDC (0.02 transfer fee)");
+    assert_eq!(amount.without_transfer_fee().to_string(), "2 USDC");
+
+    // Interest accrues on the UI amount only
+    let interest_bearing = Token {
+        info: Some(MintInfo {
+            program: TokenProgram::Token2022,
+            interest_bearing: Some(InterestBearingConfig {
+                initialization_timestamp: 0,
+                pre_update_average_rate: 0,
+                last_update_timestamp: 0,
+                current_rate: 1_000,
+            }),
+            ..mint_info
+        }),
+        ..token.clone()
+    };
+    let ui_amount = interest_bearing
+        .amount(1_000_000, 0, Some(31_556_736))
+        .ui_amount
+        .unwrap();
+    assert!((ui_amount - 0.1f64.exp()).abs() < 1e-9);
+
+    let unknown = Token {
+        info: None,
+        ..token
+    };
+    assert_eq!(unknown.amount(42, 0, None).ui_amount, None);
+    assert_eq!(
+        unknown.amount(42, 0, None).to_string(),
+        "42 base units of USDC"
+    );
+
+    let no_symbol = Token {
+        mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+        symbol: None,
+        info: None,
+        profile: None,
+    };
+    assert_eq!(no_symbol.to_string(), "EPjF…Dt1v");
+}
+
+#[test]
+fn test_transaction_kind() {
+    use TransactionKind::*;
+
+    // remove + claim + close + add
+    assert_eq!(
+        TransactionKind::from_activity(true, true, true, false),
+        Some(Rebalance)
+    );
+    // swap half, then add
+    assert_eq!(
+        TransactionKind::from_activity(true, false, false, true),
+        Some(Entry)
+    );
+    assert_eq!(
+        TransactionKind::from_activity(false, true, true, false),
+        Some(Exit)
+    );
+    assert_eq!(
+        TransactionKind::from_activity(false, false, true, false),
+        Some(Harvest)
+    );
+    assert_eq!(
+        TransactionKind::from_activity(false, false, false, true),
+        Some(Swap)
+    );
+    assert_eq!(
+        TransactionKind::from_activity(false, false, false, false),
+        None
+    );
+    assert_eq!(TransactionReport::new(Vec::new()), None);
+}
+
+#[test]
+fn test_route_name() {
+    let jupiter = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+    assert_eq!(route_name(&jupiter), "Jupiter v6");
+
+    let unknown = Pubkey::new_unique();
+    assert_eq!(route_name(&unknown), unknown.to_string());
+}
+
+#[test]
+fn test_event_context_route() {
+    use {
+        carbon_core::transaction::TransactionMetadata,
+        solana_sdk::{
+            instruction::Instruction, message::Message, message::VersionedMessage,
+            signature::Signature,
+        },
+        solana_transaction_status::TransactionStatusMeta,
+        std::sync::Arc,
+    };
+
+    let fee_payer = Pubkey::new_unique();
+    let jupiter = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+    let context = |top_level_program: Pubkey| {
+        let instruction = Instruction::new_with_bytes(top_level_program, &[], Vec::new());
+        EventContext::new(&InstructionMetadata {
+            transaction_metadata: Arc::new(TransactionMetadata {
+                slot: 300,
+                signature: Signature::new_unique(),
+                fee_payer,
+                meta: TransactionStatusMeta::default(),
+                message: VersionedMessage::Legacy(Message::new(&[instruction], Some(&fee_payer))),
+                block_time: None,
+            }),
+            stack_height: 2,
+            index: 0,
+        })
+    };
+
+    // DLMM reached from an aggregator
+    let routed = context(jupiter);
+    assert_eq!(routed.invoked_by, Some(jupiter.to_string()));
+    assert_eq!(routed.via(), " via Jupiter v6");
+
+    // Event emitted by DLMM through a self-CPI of a direct call
+    let direct = context(METEORA_PROGRAM_ID);
+    assert_eq!(direct.invoked_by, None);
+    assert_eq!(direct.via(), "");
+}
@@ -1,17 +1,41 @@
+mod account_processer;
+mod cli;
+mod datasource;
+mod events;
 mod message;
 mod processer;
+mod reconciler;
+mod records;
+mod replay;
+mod rpc_pool;
+mod sinks;
+mod state;
 mod token;
+mod token_registry;
 mod utils;
 use {
-    anyhow::Result,
+    account_processer::{MeteoraAccountProcessor, PoolStateCache, subscription_filters},
+    anyhow::{Result, bail},
+    carbon_core::pipeline::ShutdownStrategy,
     carbon_meteora_dlmm_decoder::{MeteoraDlmmDecoder, PROGRAM_ID as METEORA_PROGRAM_ID},
-    carbon_rpc_transaction_crawler_datasource::{Filters, RpcTransactionCrawler},
+    carbon_rpc_program_subscribe_datasource::RpcProgramSubscribe,
+    cli::{BackfillArgs, Command, ReplayArgs},
+    datasource::{AddressCrawler, BackfillCrawler, CrawlTarget, FailoverCrawler},
     log::info,
     message::TelegramService,
     processer::MeteoraInstructionProcessor,
-    solana_sdk::commitment_config::CommitmentConfig,
-    std::{sync::Arc, time::Duration},
-    utils::SOLANA_RPC,
+    reconciler::Reconciler,
+    replay::ReplayDatasource,
+    rpc_pool::RpcPool,
+    sinks::{LoggingSink, TelegramSink},
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+    state::{PositionRecord, StateStore},
+    std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Duration},
+    token_registry::TokenRegistry,
+    utils::{
+        ACCOUNT_STREAMING, COLD_START, CONFIG, CRAWL_MODE, FAST_CONFIRMED, LP_WALLETS, STATE_PATH,
+        TOKEN_CACHE_PATH,
+    },
 };
 
 /// Main application entry point
@@ -20,33 +44,267 @@ pub async fn main() -> Result<()> {
     // Step1. Initialize logging and environment variables
     dotenv::dotenv().ok();
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let command = cli::parse_args(std::env::args().skip(1))?;
+
+    // Step2. Set up RPC access, except for replaying fixtures and querying the state file
+    match command {
+        Command::Live { cold_start } => run_live(connect()?, cold_start || *COLD_START).await,
+        Command::Backfill(args) => run_backfill(connect()?, args).await,
+        Command::Replay(args) => run_replay(args).await,
+        Command::Record(args) => replay::record(connect()?, args).await,
+        Command::Positions { owner } => show_positions(&owner),
+    }
+}
+
+/// Set up the RPC endpoint pool, keep probing its health, and open the token cache,
+/// written back in the background
+fn connect() -> Result<Arc<RpcPool>> {
+    let rpc_pool = RpcPool::init()?;
+    rpc_pool.spawn_health_checks(Duration::from_secs(30));
+    TokenRegistry::init(TOKEN_CACHE_PATH.as_str())?.spawn_persistence(Duration::from_secs(10));
+    Ok(rpc_pool)
+}
+
+/// Follow the chain tip and alert on watched LP activity
+async fn run_live(rpc_pool: Arc<RpcPool>, cold_start: bool) -> Result<()> {
     info!("Starting Meteora DLMM transaction processor");
 
-    // Step2. Configure transaction crawler
-    let filters = Filters::new(None, None, None);
-    let transaction_crawler = RpcTransactionCrawler::new(
-        SOLANA_RPC.to_string(),              // RPC URL
-        METEORA_PROGRAM_ID,                  // Program ID to monitor
-        10,                                  // Batch limit
-        Duration::from_secs(5),              // Polling interval
-        filters,                             // Filters
-        Some(CommitmentConfig::finalized()), // Commitment config
-        1,                                   // Max Concurrent Requests
+    // Step3. Restore the crawler checkpoint, unless a cold start was requested
+    let state = Arc::new(StateStore::open(STATE_PATH.as_str())?);
+    if cold_start {
+        state.reset();
+    }
+    state.spawn_persistence(Duration::from_secs(5));
+    match state.checkpoint() {
+        Some(checkpoint) => info!(
+            "Resuming from signature {} at slot {}",
+            checkpoint.signature, checkpoint.slot
+        ),
+        None => info!("No checkpoint found, starting from the chain tip"),
+    }
+
+    // Preload the tokens of configured pools and pools with open positions
+    let mut watched_pools: Vec<Pubkey> = CONFIG
+        .pools
+        .iter()
+        .filter_map(|pool| Pubkey::from_str(&pool.lb_pair).ok())
+        .collect();
+    watched_pools.extend(state.open_position_pools());
+    watched_pools.sort();
+    watched_pools.dedup();
+    TokenRegistry::global().warm_up(&watched_pools).await;
+
+    // Step4. Configure the transaction datasource. In the fast path transactions
+    // are processed at confirmed and reconciled at finalized later on.
+    let commitment = if *FAST_CONFIRMED {
+        info!("Processing at confirmed commitment with finalized reconciliation");
+        CommitmentConfig::confirmed()
+    } else {
+        CommitmentConfig::finalized()
+    };
+    let mut pipeline = carbon_core::pipeline::Pipeline::builder().metrics_flush_interval(3);
+    match CRAWL_MODE.as_str() {
+        "program" => {
+            // The crawler resumes from the checkpoint and fails over to another
+            // endpoint when the current one turns unhealthy
+            let transaction_crawler = FailoverCrawler::new(
+                rpc_pool.clone(),       // RPC endpoint pool
+                state.clone(),          // Checkpoint to resume from
+                METEORA_PROGRAM_ID,     // Program ID to monitor
+                10,                     // Batch limit
+                Duration::from_secs(5), // Polling interval
+                commitment,             // Commitment config
+                1,                      // Max Concurrent Requests
+            );
+            pipeline = pipeline.datasource(transaction_crawler);
+            info!("Configured transaction crawler for Meteora DLMM program");
+        }
+        "wallets" => {
+            let targets = LP_WALLETS
+                .iter()
+                .filter_map(|wallet| Pubkey::from_str(wallet).ok())
+                .map(|address| CrawlTarget {
+                    address,
+                    polling_interval: Duration::from_secs(5),
+                })
+                .collect::<Vec<_>>();
+            if targets.is_empty() {
+                bail!("CRAWL_MODE=wallets needs at least one valid address in `lp_wallets`");
+            }
+            info!(
+                "Configured signature crawlers for {} LP wallets",
+                targets.len()
+            );
+            let wallet_crawler = AddressCrawler::new(
+                rpc_pool.clone(),   // RPC endpoint pool
+                state.clone(),      // Per-wallet checkpoints
+                "wallet",           // Checkpoint stream name
+                targets,            // Wallets to crawl
+                METEORA_PROGRAM_ID, // Only keep DLMM transactions
+                100,                // Batch limit
+                commitment,         // Commitment config
+            );
+            pipeline = pipeline.datasource(wallet_crawler);
+        }
+        "pools" => {
+            let targets = CONFIG
+                .pools
+                .iter()
+                .map(|pool| {
+                    Ok(CrawlTarget {
+                        address: Pubkey::from_str(&pool.lb_pair)?,
+                        polling_interval: Duration::from_secs(pool.polling_interval_secs),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if targets.is_empty() {
+                bail!("CRAWL_MODE=pools needs at least one entry in `pools`");
+            }
+            info!("Configured signature crawlers for {} pools", targets.len());
+            let pool_crawler = AddressCrawler::new(
+                rpc_pool.clone(),   // RPC endpoint pool
+                state.clone(),      // Per-pool checkpoints
+                "pool",             // Checkpoint stream name
+                targets,            // Pools to crawl
+                METEORA_PROGRAM_ID, // Only keep DLMM transactions
+                100,                // Batch limit
+                commitment,         // Commitment config
+            );
+            pipeline = pipeline.datasource(pool_crawler);
+        }
+        other => bail!(
+            "Unknown CRAWL_MODE `{}`, expected `program`, `wallets` or `pools`",
+            other
+        ),
+    }
+
+    // Step5. Build the processing pipeline
+    let telegram_service = Arc::new(TelegramService::new());
+    let mut telegram_sink = TelegramSink::new(telegram_service.clone());
+    let mut instruction_processor = MeteoraInstructionProcessor::new().with_state(state.clone());
+    if *FAST_CONFIRMED {
+        let reconciler = Arc::new(Reconciler::new(
+            rpc_pool.clone(),
+            state,
+            Some(telegram_service),
+        ));
+        reconciler.spawn();
+        telegram_sink = telegram_sink.with_reconciler(reconciler.clone());
+        instruction_processor = instruction_processor.with_reconciler(reconciler);
+    }
+    instruction_processor = instruction_processor
+        .with_sink(Arc::new(LoggingSink))
+        .with_sink(Arc::new(telegram_sink));
+
+    // Step6. Optionally stream DLMM accounts to keep live pool and position state:
+    // pools, positions of LP wallets and bin arrays of watched pools only
+    if *ACCOUNT_STREAMING {
+        let pool_state = Arc::new(PoolStateCache::new());
+        let wallets: Vec<Pubkey> = LP_WALLETS
+            .iter()
+            .filter_map(|wallet| Pubkey::from_str(wallet).ok())
+            .collect();
+        let subscriptions = subscription_filters(&wallets, &watched_pools, commitment);
+        info!(
+            "Configured {} account subscriptions for Meteora DLMM program",
+            subscriptions.len()
+        );
+        for filters in subscriptions {
+            pipeline = pipeline.datasource(RpcProgramSubscribe::new(
+                rpc_pool.websocket_url(), // Websocket URL
+                filters,                  // Program ID and account filters
+            ));
+        }
+        pipeline = pipeline.account(
+            MeteoraDlmmDecoder,
+            MeteoraAccountProcessor::new(pool_state.clone()),
+        );
+        instruction_processor = instruction_processor.with_pool_state(pool_state);
+    }
+
+    // Step7. Run the processing pipeline
+    pipeline
+        .instruction(MeteoraDlmmDecoder, instruction_processor)
+        .build()?
+        .run()
+        .await?;
+    state.flush().await;
+    TokenRegistry::global().flush().await;
+
+    info!("Pipeline completed successfully");
+    Ok(())
+}
+
+/// Replay a bounded historical range through the processor, then exit
+async fn run_backfill(rpc_pool: Arc<RpcPool>, args: BackfillArgs) -> Result<()> {
+    info!("Starting Meteora DLMM backfill: {:?}", args);
+
+    // Step3. Notifications are opt-in, historical alerts are usually noise
+    let telegram_service = args.notify.then(|| Arc::new(TelegramService::new()));
+
+    // Step4. Configure the bounded crawler, it stops at the lower bound
+    let backfill_crawler = BackfillCrawler::new(
+        rpc_pool,                      // RPC endpoint pool
+        METEORA_PROGRAM_ID,            // Program ID to backfill
+        args,                          // Range bounds
+        1000,                          // Signatures per page
+        5,                             // Max Concurrent Requests
+        CommitmentConfig::finalized(), // Commitment config
     );
-    info!("Configured transaction crawler for Meteora DLMM program");
 
-    // Step3. Build and run the processing pipeline
+    // Step5. Build and run the processing pipeline. The live checkpoint is not
+    // touched, a backfill must not move the resume point of the live agent.
     carbon_core::pipeline::Pipeline::builder()
-        .datasource(transaction_crawler)
+        .datasource(backfill_crawler)
+        .shutdown_strategy(ShutdownStrategy::ProcessPending)
         .metrics_flush_interval(3)
-        .instruction(
-            MeteoraDlmmDecoder,
-            MeteoraInstructionProcessor::new(Arc::new(TelegramService::new())),
-        )
+        .instruction(MeteoraDlmmDecoder, event_processor(telegram_service))
         .build()?
         .run()
         .await?;
+    TokenRegistry::global().flush().await;
 
-    info!("Pipeline completed successfully");
+    info!("Backfill completed successfully");
     Ok(())
 }
+
+/// Replay recorded transaction fixtures through the processor, then exit
+async fn run_replay(args: ReplayArgs) -> Result<()> {
+    info!("Starting Meteora DLMM replay: {:?}", args);
+
+    // Step3. Notifications are opt-in, replayed alerts are usually noise
+    let telegram_service = args.notify.then(|| Arc::new(TelegramService::new()));
+
+    // Step4. Build and run the processing pipeline over the fixtures
+    carbon_core::pipeline::Pipeline::builder()
+        .datasource(ReplayDatasource::new(args.files, args.original_timing))
+        .shutdown_strategy(ShutdownStrategy::ProcessPending)
+        .metrics_flush_interval(3)
+        .instruction(MeteoraDlmmDecoder, event_processor(telegram_service))
+        .build()?
+        .run()
+        .await?;
+
+    info!("Replay completed successfully");
+    Ok(())
+}
+
+/// Print the registered positions of `owner` and their lifecycle as JSON
+fn show_positions(owner: &Pubkey) -> Result<()> {
+    let state = StateStore::open(STATE_PATH.as_str())?;
+    let positions: BTreeMap<String, PositionRecord> =
+        state.positions_of(owner).into_iter().collect();
+    println!("{}", serde_json::to_string_pretty(&positions)?);
+    Ok(())
+}
+
+/// Processor logging every event, and alerting on Telegram when `telegram_service` is set
+fn event_processor(telegram_service: Option<Arc<TelegramService>>) -> MeteoraInstructionProcessor {
+    let instruction_processor = MeteoraInstructionProcessor::new().with_sink(Arc::new(LoggingSink));
+    match telegram_service {
+        Some(telegram_service) => {
+            instruction_processor.with_sink(Arc::new(TelegramSink::new(telegram_service)))
+        }
+        None => instruction_processor,
+    }
+}
@@ -6,15 +6,16 @@ use teloxide::types::ChatId;
 // --- Service Definition ---
 #[derive(Clone)]
 pub struct TelegramService {
-    bot: Throttle<Bot>, // Bot instance for actual API calls
-    group_id: i64,      // Stores the default group_id in the service
+    bot: Throttle<Bot>,          // Bot instance for actual API calls
+    group_id: i64,               // Stores the default group_id in the service
+    alert_group_id: Option<i64>, // Group for high-severity alerts, the default group if unset
 }
 
 impl TelegramService {
     /// Create a new TelegramService instance.
     ///  
     /// # Panics  
-    /// * If `group_id_str` cannot be parsed as i64.  
+    /// * If `group_id_str` or `TELEGRAM_ALERT_GROUP_ID` cannot be parsed as i64.  
     pub fn new() -> Self {
         // Telegram Bot Token.
         let bot_token =
@@ -26,8 +27,17 @@ impl TelegramService {
         let group_id: i64 = group_id_str
             .parse()
             .expect("Invalid Group ID format, should be i64 number");
+        // Optional group for high-severity alerts.
+        let alert_group_id = std::env::var("TELEGRAM_ALERT_GROUP_ID").ok().map(|id| {
+            id.parse()
+                .expect("Invalid alert Group ID format, should be i64 number")
+        });
 
-        TelegramService { bot, group_id }
+        TelegramService {
+            bot,
+            group_id,
+            alert_group_id,
+        }
     }
 
     /// Send a message to the configured default group.  
@@ -48,6 +58,25 @@ impl TelegramService {
         Ok(())
     }
 
+    /// Send a high-severity alert to the alert group, or to the default group
+    /// with a marker if no alert group is configured.
+    ///  
+    /// # Arguments  
+    /// * `message` - Message text to send.  
+    ///  
+    /// # Returns  
+    /// * `Ok(())` - If the message is sent successfully.  
+    /// * `Err(RequestError)` - If there is an error during sending.
+    pub async fn send_high_severity(&self, message: &str) -> Result<(), RequestError> {
+        match self.alert_group_id {
+            Some(alert_group_id) => self.send_message_to_group(alert_group_id, message).await,
+            None => {
+                self.send_message(&format!("[HIGH SEVERITY]\n{}", message))
+                    .await
+            }
+        }
+    }
+
     /// Send a message to a specified group ID (if sending to a different group is needed).  
     ///  
     /// # Arguments  
@@ -1,190 +1,508 @@
-use carbon_meteora_dlmm_decoder::instructions::remove_liquidity::RemoveLiquidity;
 use log::{debug, warn};
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use std::fmt;
 
 use crate::{
-    message::TelegramService,
-    token::get_token_metadata,
-    utils::{CLIENT_ACCOUNT_FILTERING, LP_WALLETS},
+    account_processer::{PoolStateCache, base_fee_bps, fetch_pool},
+    events::{
+        AdminChangeEvent, ClaimEvent, EventContext, LiquidityAddedEvent, LiquidityRemovedEvent,
+        MeteoraEvent, PoolCreatedEvent, PositionClosedEvent, PositionOpenedEvent,
+        SwapExecutedEvent, TransactionReport,
+    },
+    reconciler::Reconciler,
+    records::{
+        AdminChange, Claimed, PoolCreated, PositionOpened, decode_admin_change, decode_claimed,
+        decode_liquidity_added, decode_liquidity_removed, decode_pool_created,
+        decode_position_opened, decode_swap_executed, one_side_lb_pair,
+    },
+    sinks::EventSink,
+    state::{HarvestTotals, PositionActivity, PositionEvent, StateStore},
+    utils::{CLIENT_ACCOUNT_FILTERING, CONFIG, LP_WALLETS},
 };
 use {
     async_trait::async_trait,
     carbon_core::{
-        deserialize::ArrangeAccounts,
         error::CarbonResult,
-        instruction::{DecodedInstruction, InstructionMetadata, NestedInstructions},
+        instruction::{
+            DecodedInstruction, InstructionDecoder, InstructionMetadata, NestedInstructions,
+        },
         metrics::MetricsCollection,
         processor::Processor,
+        transaction::TransactionMetadata,
     },
-    carbon_meteora_dlmm_decoder::instructions::{
-        MeteoraDlmmInstruction, add_liquidity::AddLiquidity, swap::Swap,
+    carbon_meteora_dlmm_decoder::{
+        MeteoraDlmmDecoder, PROGRAM_ID, accounts::lb_pair::LbPair,
+        instructions::MeteoraDlmmInstruction,
     },
-    log::{error, info},
+    log::info,
     std::sync::Arc,
 };
-/// Processor for Meteora DLMM instructions
+/// Where an account key of a transaction comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKeySource {
+    Static,
+    LookupTableWritable,
+    LookupTableReadonly,
+}
+
+impl fmt::Display for AccountKeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountKeySource::Static => write!(f, "static"),
+            AccountKeySource::LookupTableWritable => write!(f, "ALT writable"),
+            AccountKeySource::LookupTableReadonly => write!(f, "ALT readonly"),
+        }
+    }
+}
+
+/// Whether `wallet` is one of the watched LP wallets
+fn is_lp_wallet(wallet: &Pubkey) -> bool {
+    let wallet = wallet.to_string();
+    LP_WALLETS.iter().any(|lp_wallet| lp_wallet == &wallet)
+}
+
+/// Processor turning Meteora DLMM instructions into `MeteoraEvent`s for the event sinks
 pub struct MeteoraInstructionProcessor {
-    telegram_service: Arc<TelegramService>,
+    sinks: Vec<Arc<dyn EventSink>>,
+    state: Option<Arc<StateStore>>,
+    pool_state: Option<Arc<PoolStateCache>>,
+    reconciler: Option<Arc<Reconciler>>,
+    // Transaction currently being processed, committed once the next one arrives
+    pending_transaction: Option<(Signature, u64)>,
+    // Pools announced in the current transaction, by their instruction or event
+    announced_pools: Vec<Pubkey>,
+    // Admin changes alerted in the current transaction
+    alerted_admin_changes: Vec<(&'static str, Pubkey)>,
+    // LP and swap events of the current transaction, reported together
+    transaction_events: Vec<MeteoraEvent>,
+    // DLMM instructions of the current transaction not processed yet
+    remaining_instructions: usize,
 }
 
 impl MeteoraInstructionProcessor {
-    pub fn new(telegram_service: Arc<TelegramService>) -> Self {
-        Self { telegram_service }
+    pub fn new() -> Self {
+        Self {
+            sinks: Vec::new(),
+            state: None,
+            pool_state: None,
+            reconciler: None,
+            pending_transaction: None,
+            announced_pools: Vec::new(),
+            alerted_admin_changes: Vec::new(),
+            transaction_events: Vec::new(),
+            remaining_instructions: 0,
+        }
     }
-}
 
-#[async_trait]
-impl Processor for MeteoraInstructionProcessor {
-    type InputType = (
-        InstructionMetadata,
-        DecodedInstruction<MeteoraDlmmInstruction>,
-        NestedInstructions,
-    );
+    /// Emit every event to `sink`, in addition to the sinks added before
+    pub fn with_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
 
-    async fn process(
+    /// Persist a checkpoint of every fully processed transaction into `state`
+    pub fn with_state(mut self, state: Arc<StateStore>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Enrich alerts with the live pool state streamed from the program accounts
+    pub fn with_pool_state(mut self, pool_state: Arc<PoolStateCache>) -> Self {
+        self.pool_state = Some(pool_state);
+        self
+    }
+
+    /// Hand every transaction to `reconciler` so it is checked at finalized commitment
+    pub fn with_reconciler(mut self, reconciler: Arc<Reconciler>) -> Self {
+        self.reconciler = Some(reconciler);
+        self
+    }
+
+    async fn emit(&self, event: &MeteoraEvent) {
+        for sink in &self.sinks {
+            sink.handle(event).await;
+        }
+    }
+
+    /// State of `lb_pair` from the account stream, fetched when it has not been streamed
+    async fn pool(&self, lb_pair: &Pubkey) -> Option<LbPair> {
+        let streamed = self
+            .pool_state
+            .as_ref()
+            .and_then(|pool_state| pool_state.pool(lb_pair));
+        match streamed {
+            Some(pool) => Some(pool),
+            None => fetch_pool(lb_pair).await,
+        }
+    }
+
+    /// New pool passing the `new_pools` filters, once per transaction
+    async fn pool_created_event(
         &mut self,
-        data: Self::InputType,
-        _metrics: Arc<MetricsCollection>,
-    ) -> CarbonResult<()> {
-        let (_instruction_metadata, decoded_instruction, _nested_instructions) = data;
-        debug!(
-            "Decoded instruction data: {}",
-            serde_json::to_string(&decoded_instruction.data)
-                .unwrap_or("json decode error".to_string())
+        context: EventContext,
+        pool_created: PoolCreated,
+    ) -> Option<MeteoraEvent> {
+        let new_pools = CONFIG.new_pools.as_ref()?;
+        if self.announced_pools.contains(&pool_created.lb_pair) {
+            return None;
+        }
+        self.announced_pools.push(pool_created.lb_pair);
+
+        // `InitializeLbPair` takes its fee from a preset, read it back from the new pool
+        let fee_bps = match pool_created.base_fee_bps {
+            Some(fee_bps) => Some(fee_bps),
+            None => self.pool(&pool_created.lb_pair).await.map(|pool| {
+                base_fee_bps(
+                    pool.parameters.base_factor,
+                    pool.bin_step,
+                    pool.parameters.base_fee_power_factor,
+                )
+            }),
+        };
+        if !new_pools.accepts(
+            &pool_created.token_x_mint.to_string(),
+            &pool_created.token_y_mint.to_string(),
+            pool_created.bin_step,
+            fee_bps,
+        ) {
+            debug!("New pool {} filtered out", pool_created.lb_pair);
+            return None;
+        }
+        Some(MeteoraEvent::PoolCreated(
+            PoolCreatedEvent::new(context, &pool_created, fee_bps).await,
+        ))
+    }
+
+    /// Whether a watched LP wallet has an open position in `lb_pair`, according to
+    /// the position registry or the account stream
+    fn is_watched_pool(&self, lb_pair: &Pubkey) -> bool {
+        let registered = self
+            .state
+            .as_ref()
+            .is_some_and(|state| !state.open_positions_in(lb_pair).is_empty());
+        let streamed = self
+            .pool_state
+            .as_ref()
+            .is_some_and(|pool_state| pool_state.is_watched_pool(lb_pair));
+        registered || streamed
+    }
+
+    /// Admin change of any pool, marked as watched when LP positions are at stake
+    fn admin_change_event(
+        &mut self,
+        context: EventContext,
+        admin_change: AdminChange,
+    ) -> Option<MeteoraEvent> {
+        // The event repeats the instruction it is emitted by
+        if admin_change.instruction == "FeeParameterUpdateEvent"
+            && self
+                .alerted_admin_changes
+                .contains(&("UpdateFeeParameters", admin_change.lb_pair))
+        {
+            return None;
+        }
+        let watched = self.is_watched_pool(&admin_change.lb_pair);
+        self.alerted_admin_changes
+            .push((admin_change.instruction, admin_change.lb_pair));
+        let pool_state = self
+            .pool_state
+            .as_ref()
+            .and_then(|pool_state| pool_state.summary(&admin_change.lb_pair));
+        Some(MeteoraEvent::AdminChange(AdminChangeEvent::new(
+            context,
+            &admin_change,
+            pool_state,
+            watched,
+        )))
+    }
+
+    /// Register a new position of a watched LP wallet
+    fn position_opened_event(
+        &self,
+        transaction_metadata: &TransactionMetadata,
+        context: EventContext,
+        opened: &PositionOpened,
+    ) -> Option<MeteoraEvent> {
+        let state = self.state.as_ref()?;
+        if !is_lp_wallet(&opened.owner) {
+            return None;
+        }
+        let activity = PositionActivity::new(
+            &transaction_metadata.signature,
+            transaction_metadata.slot,
+            transaction_metadata.block_time,
+            PositionEvent::Opened,
         );
+        state
+            .open_position(
+                &opened.position,
+                &opened.owner,
+                &opened.lb_pair,
+                opened.bin_range,
+                activity,
+            )
+            .then(|| MeteoraEvent::PositionOpened(PositionOpenedEvent::new(context, opened)))
+    }
 
-        let transaction_metadata = &_instruction_metadata.transaction_metadata;
+    /// Append an event to the lifecycle of a registered position, returning the
+    /// closing event when it closes the position
+    fn track_position_event(
+        &self,
+        transaction_metadata: &TransactionMetadata,
+        context: &EventContext,
+        position: &Pubkey,
+        event: PositionEvent,
+    ) -> Option<MeteoraEvent> {
+        let state = self.state.as_ref()?;
+        let closed = event == PositionEvent::Closed;
+        let activity = PositionActivity::new(
+            &transaction_metadata.signature,
+            transaction_metadata.slot,
+            transaction_metadata.block_time,
+            event,
+        );
+        let record = state.record_position_activity(position, activity)?;
+        closed.then(|| {
+            MeteoraEvent::PositionClosed(PositionClosedEvent::new(
+                context.clone(),
+                position,
+                &record,
+            ))
+        })
+    }
+
+    /// Add a claim of a watched LP wallet to its running harvest totals, returning
+    /// the new totals of the position and of the wallet
+    fn record_claim(
+        &self,
+        signature: &Signature,
+        claimed: &Claimed,
+    ) -> Option<(HarvestTotals, HarvestTotals)> {
+        let state = self.state.as_ref()?;
+        if !is_lp_wallet(&claimed.owner) {
+            return None;
+        }
+        let totals = state.record_claim(
+            signature,
+            &claimed.position,
+            &claimed.owner,
+            claimed.reward_index,
+            &claimed.amounts,
+        );
+        if totals.is_none() {
+            debug!("Claim in {} already counted", signature);
+        }
+        totals
+    }
+
+    /// Events of one decoded DLMM instruction
+    async fn instruction_events(
+        &mut self,
+        instruction_metadata: &InstructionMetadata,
+        decoded_instruction: &DecodedInstruction<MeteoraDlmmInstruction>,
+        nested_instructions: &NestedInstructions,
+    ) -> Vec<MeteoraEvent> {
+        let transaction_metadata = &instruction_metadata.transaction_metadata;
         let fee_payer = transaction_metadata.fee_payer;
-        let account_keys = transaction_metadata.message.static_account_keys();
-        if *CLIENT_ACCOUNT_FILTERING && !check_accounts_in_client(fee_payer, account_keys) {
-            warn!("  CLIENT_ACCOUNT_FILTERING, check_accounts_in_client false");
-            return Ok(());
+        let account_keys = transaction_account_keys(transaction_metadata);
+        let instruction_accounts = resolve_instruction_accounts(
+            instruction_metadata,
+            &account_keys,
+            &decoded_instruction.accounts,
+        );
+        let context = EventContext::new(instruction_metadata);
+
+        // Pool launches are of interest whichever wallet creates them
+        if let Some(pool_created) =
+            decode_pool_created(&decoded_instruction.data, &instruction_accounts)
+        {
+            return self
+                .pool_created_event(context, pool_created)
+                .await
+                .into_iter()
+                .collect();
+        }
+        // So are admin changes, which are not signed by LP wallets
+        if let Some(admin_change) =
+            decode_admin_change(&decoded_instruction.data, &instruction_accounts)
+        {
+            return self
+                .admin_change_event(context, admin_change)
+                .into_iter()
+                .collect();
         }
 
-        // Check if fee_payer is in LP_WALLETS
+        if *CLIENT_ACCOUNT_FILTERING
+            && !check_accounts_in_client(fee_payer, &account_keys, &LP_WALLETS)
+        {
+            warn!(
+                "  CLIENT_ACCOUNT_FILTERING, check_accounts_in_client false{}",
+                context.via()
+            );
+            return Vec::new();
+        }
+        if let Some(invoked_by) = &context.invoked_by {
+            info!(
+                "  DLMM reached{} ({}) at stack height {}, signer {}",
+                context.via(),
+                invoked_by,
+                context.stack_height,
+                context.signer
+            );
+        }
 
+        let mut events = Vec::new();
         match &decoded_instruction.data {
-            MeteoraDlmmInstruction::AddLiquidityEvent(event) => {
-                info!("AddLiquidityEvent details:");
-                info!("  lb_pair: {}", event.lb_pair);
-                info!("  from: {}", event.from);
-                info!("  position: {}", event.position);
-                info!("  amounts: [{}, {}]", event.amounts[0], event.amounts[1]);
-                info!("  active_bin_id: {}", event.active_bin_id);
-            }
-            MeteoraDlmmInstruction::RemoveLiquidityEvent(event) => {
-                info!("RemoveLiquidityEvent details:");
-                info!("  lb_pair: {}", event.lb_pair);
-                info!("  from: {}", event.from);
-                info!("  position: {}", event.position);
-                info!("  amounts: [{}, {}]", event.amounts[0], event.amounts[1]);
-                info!("  active_bin_id: {}", event.active_bin_id);
-            }
-            MeteoraDlmmInstruction::AddLiquidity(_liquidity_parameter) => {
-                let accounts = AddLiquidity::arrange_accounts(&decoded_instruction.accounts);
-                if let Some(accounts) = accounts {
-                    info!("AddLiquidity Instruction details:");
-                    let token_x = accounts.token_x_mint;
-                    let token_y = accounts.token_y_mint;
-                    // fetch token metadata
-                    match get_token_metadata(token_x).await {
-                        Ok((_, symbol)) => {
-                            info!("  symbol_x: {}", symbol);
-                        }
-                        Err(e) => {
-                            error!("  Failed to fetch token_x metadata: {}", e);
-                        }
-                    };
-
-                    match get_token_metadata(token_y).await {
-                        Ok((_, symbol)) => {
-                            info!("  symbol_y: {}", symbol);
-                        }
-                        Err(e) => {
-                            error!("  Failed to fetch token_y metadata: {}", e);
-                        }
+            MeteoraDlmmInstruction::AddLiquidity(_)
+            | MeteoraDlmmInstruction::AddLiquidityByWeight(_)
+            | MeteoraDlmmInstruction::AddLiquidityByStrategy(_)
+            | MeteoraDlmmInstruction::AddLiquidityByStrategyOneSide(_)
+            | MeteoraDlmmInstruction::AddLiquidityOneSide(_)
+            | MeteoraDlmmInstruction::AddLiquidityOneSidePrecise(_) => {
+                // One-sided deposits only name their mint, the pool tells its side
+                let pool_mints =
+                    match one_side_lb_pair(&decoded_instruction.data, &instruction_accounts) {
+                        Some(lb_pair) => self
+                            .pool(&lb_pair)
+                            .await
+                            .map(|pool| (pool.token_x_mint, pool.token_y_mint)),
+                        None => None,
                     };
-                    let amount_x = _liquidity_parameter.liquidity_parameter.amount_x;
-                    info!("  amount_x: {}", amount_x);
-                    let amount_y = _liquidity_parameter.liquidity_parameter.amount_x;
-                    info!("  amount_y: {}", amount_y);
+                let liquidity_added = decode_liquidity_added(
+                    &decoded_instruction.data,
+                    &instruction_accounts,
+                    pool_mints,
+                );
+                if let Some(liquidity_added) = liquidity_added {
+                    self.track_position_event(
+                        transaction_metadata,
+                        &context,
+                        &liquidity_added.position,
+                        PositionEvent::Added {
+                            amount_x: liquidity_added.amount_x,
+                            amount_y: liquidity_added.amount_y,
+                        },
+                    );
+                    events.push(MeteoraEvent::LiquidityAdded(
+                        LiquidityAddedEvent::new(context, &liquidity_added).await,
+                    ));
                 }
             }
-            MeteoraDlmmInstruction::RemoveLiquidity(_liquidity_parameter) => {
-                let accounts = RemoveLiquidity::arrange_accounts(&decoded_instruction.accounts);
-                if let Some(accounts) = accounts {
-                    info!("RemoveLiquidity Instruction details:");
-                    let token_x = accounts.token_x_mint;
-                    let token_y = accounts.token_y_mint;
-                    // fetch token metadata
-                    match get_token_metadata(token_x).await {
-                        Ok((_, symbol)) => {
-                            info!("  symbol_x: {}", symbol);
-                        }
-                        Err(e) => {
-                            error!("  Failed to fetch token_x metadata: {}", e);
-                        }
-                    };
-
-                    match get_token_metadata(token_y).await {
-                        Ok((_, symbol)) => {
-                            info!("  symbol_y: {}", symbol);
-                        }
-                        Err(e) => {
-                            error!("  Failed to fetch token_y metadata: {}", e);
+            MeteoraDlmmInstruction::RemoveLiquidity(_)
+            | MeteoraDlmmInstruction::RemoveLiquidityByRange(_)
+            | MeteoraDlmmInstruction::RemoveAllLiquidity(_)
+            | MeteoraDlmmInstruction::ClosePosition(_) => {
+                let position_range = instruction_accounts.first().and_then(|position| {
+                    let streamed = self
+                        .pool_state
+                        .as_ref()
+                        .and_then(|pool_state| pool_state.position_range(&position.pubkey));
+                    streamed
+                        .or_else(|| self.state.as_ref()?.position(&position.pubkey)?.bin_range())
+                });
+                let liquidity_removed = decode_liquidity_removed(
+                    &decoded_instruction.data,
+                    &instruction_accounts,
+                    nested_instructions,
+                    position_range,
+                );
+                if let Some(liquidity_removed) = liquidity_removed {
+                    let position_event = if liquidity_removed.instruction == "ClosePosition" {
+                        PositionEvent::Closed
+                    } else {
+                        PositionEvent::Removed {
+                            amount_x: liquidity_removed.amount_x,
+                            amount_y: liquidity_removed.amount_y,
+                            fully_exited: liquidity_removed.fully_exited,
                         }
                     };
-                    let bin_liquidity_removal = &_liquidity_parameter.bin_liquidity_removal;
-                    info!(
-                        "  bin_liquidity_removal_len: {}",
-                        bin_liquidity_removal.len()
+                    let closed = self.track_position_event(
+                        transaction_metadata,
+                        &context,
+                        &liquidity_removed.position,
+                        position_event,
                     );
+                    events.push(MeteoraEvent::LiquidityRemoved(
+                        LiquidityRemovedEvent::new(context, &liquidity_removed).await,
+                    ));
+                    events.extend(closed);
                 }
             }
-            MeteoraDlmmInstruction::Swap(swap_parameters) => {
-                let accounts = Swap::arrange_accounts(&decoded_instruction.accounts);
-                if let Some(accounts) = accounts {
-                    info!("=======>Swap Instruction details:");
-                    let token_x = accounts.token_x_mint;
-                    let token_y = accounts.token_y_mint;
-                    // fetch token metadata
-                    let symbol_x = match get_token_metadata(token_x).await {
-                        Ok((_, symbol)) => {
-                            info!("  token_x: {}", symbol);
-                            Some(symbol)
-                        }
-                        Err(e) => {
-                            error!("  Failed to fetch user_token_in metadata: {}", e);
-                            None
-                        }
-                    };
-                    let symbol_y = match get_token_metadata(token_y).await {
-                        Ok((_, symbol)) => {
-                            info!("  token_y: {}", symbol);
-                            Some(symbol)
-                        }
-                        Err(e) => {
-                            error!("  Failed to fetch token_y metadata: {}", e);
-                            None
-                        }
-                    };
-                    let amount_in = swap_parameters.amount_in;
-                    info!("  amount_in: {}", amount_in);
-                    let min_amount_out = swap_parameters.min_amount_out;
-                    info!("  min_amount_out: {}", min_amount_out);
-
-                    if symbol_x.is_some() && symbol_y.is_some() {
-                        let message = format!(
-                            "Swap Instruction:\nToken X: {}\nToken Y: {}\nAmount In: {}\nMin Amount Out: {}",
-                            symbol_x.unwrap(),
-                            symbol_y.unwrap(),
-                            amount_in,
-                            min_amount_out
-                        );
-                        self.telegram_service.send_message(&message).await.unwrap();
-                    }
+            MeteoraDlmmInstruction::Swap(_)
+            | MeteoraDlmmInstruction::SwapExactOut(_)
+            | MeteoraDlmmInstruction::SwapWithPriceImpact(_) => {
+                let swap_executed = decode_swap_executed(
+                    &decoded_instruction.data,
+                    &instruction_accounts,
+                    nested_instructions,
+                );
+                if let Some(swap_executed) = swap_executed {
+                    let pool_state = self
+                        .pool_state
+                        .as_ref()
+                        .and_then(|pool_state| pool_state.summary(&swap_executed.lb_pair));
+                    events.push(MeteoraEvent::Swap(
+                        SwapExecutedEvent::new(context, &swap_executed, pool_state).await,
+                    ));
+                }
+            }
+            MeteoraDlmmInstruction::InitializePosition(_)
+            | MeteoraDlmmInstruction::InitializePositionPda(_)
+            | MeteoraDlmmInstruction::PositionCreateEvent(_) => {
+                let opened =
+                    decode_position_opened(&decoded_instruction.data, &instruction_accounts);
+                if let Some(opened) = opened {
+                    events.extend(self.position_opened_event(
+                        transaction_metadata,
+                        context,
+                        &opened,
+                    ));
                 }
             }
+            MeteoraDlmmInstruction::PositionCloseEvent(event) => {
+                events.extend(self.track_position_event(
+                    transaction_metadata,
+                    &context,
+                    &event.position,
+                    PositionEvent::Closed,
+                ));
+            }
+            MeteoraDlmmInstruction::ClaimFee(_) | MeteoraDlmmInstruction::ClaimReward(_) => {
+                let claimed = decode_claimed(
+                    &decoded_instruction.data,
+                    &instruction_accounts,
+                    nested_instructions,
+                );
+                if let Some(claimed) = claimed {
+                    self.track_position_event(
+                        transaction_metadata,
+                        &context,
+                        &claimed.position,
+                        PositionEvent::Claimed {
+                            reward_index: claimed.reward_index,
+                            amounts: claimed
+                                .amounts
+                                .iter()
+                                .map(|(mint, amount)| (mint.to_string(), *amount))
+                                .collect(),
+                        },
+                    );
+                    let harvests = self.record_claim(&transaction_metadata.signature, &claimed);
+                    events.push(MeteoraEvent::Claim(
+                        ClaimEvent::new(context, &claimed, harvests).await,
+                    ));
+                }
+            }
+            // Events repeating an instruction, reported through the instruction itself
+            MeteoraDlmmInstruction::AddLiquidityEvent(_)
+            | MeteoraDlmmInstruction::RemoveLiquidityEvent(_)
+            | MeteoraDlmmInstruction::ClaimFeeEvent(_)
+            | MeteoraDlmmInstruction::ClaimRewardEvent(_) => {}
             _ => {
                 info!(
                     "Instruction type: {}",
@@ -201,32 +519,240 @@ impl Processor for MeteoraInstructionProcessor {
 
         // Helper function to get the instruction name without listing all types
         fn get_instruction_name(instruction: &MeteoraDlmmInstruction) -> String {
-            match instruction {
-                MeteoraDlmmInstruction::AddLiquidityEvent(_) => "AddLiquidityEvent".to_string(),
-                MeteoraDlmmInstruction::RemoveLiquidityEvent(_) => {
-                    "RemoveLiquidityEvent".to_string()
-                }
-                _ => format!("{:?}", instruction)
-                    .split("(")
-                    .next()
-                    .unwrap_or("Unknown")
-                    .to_string(),
+            format!("{:?}", instruction)
+                .split("(")
+                .next()
+                .unwrap_or("Unknown")
+                .to_string()
+        }
+
+        events
+    }
+
+    /// Report the activity of the current transaction, once
+    async fn flush_report(&mut self) {
+        let events = std::mem::take(&mut self.transaction_events);
+        let Some(report) = TransactionReport::new(events) else {
+            return;
+        };
+        for sink in &self.sinks {
+            sink.handle_report(&report).await;
+        }
+    }
+
+    /// Instructions of one transaction are delivered back to back, so the previous
+    /// transaction is complete as soon as an instruction of a new one shows up.
+    async fn track_transaction(&mut self, transaction_metadata: &TransactionMetadata) {
+        let signature = transaction_metadata.signature;
+        let slot = transaction_metadata.slot;
+        if matches!(self.pending_transaction, Some((pending, _)) if pending == signature) {
+            return;
+        }
+        // Usually already reported after its last DLMM instruction
+        self.flush_report().await;
+        self.remaining_instructions = dlmm_instruction_count(transaction_metadata);
+        self.announced_pools.clear();
+        self.alerted_admin_changes.clear();
+        if let Some(reconciler) = &self.reconciler {
+            reconciler.track(
+                signature,
+                slot,
+                *transaction_metadata.message.recent_blockhash(),
+            );
+        }
+        if let (Some(state), Some((pending, pending_slot))) = (
+            &self.state,
+            self.pending_transaction.replace((signature, slot)),
+        ) {
+            state.record_processed(&pending, pending_slot);
+        }
+    }
+}
+
+#[async_trait]
+impl Processor for MeteoraInstructionProcessor {
+    type InputType = (
+        InstructionMetadata,
+        DecodedInstruction<MeteoraDlmmInstruction>,
+        NestedInstructions,
+    );
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (_instruction_metadata, decoded_instruction, _nested_instructions) = data;
+        debug!(
+            "Decoded instruction data: {}",
+            serde_json::to_string(&decoded_instruction.data)
+                .unwrap_or("json decode error".to_string())
+        );
+
+        let transaction_metadata = &_instruction_metadata.transaction_metadata;
+        self.track_transaction(transaction_metadata).await;
+
+        let events = self
+            .instruction_events(
+                &_instruction_metadata,
+                &decoded_instruction,
+                &_nested_instructions,
+            )
+            .await;
+        for event in events {
+            self.emit(&event).await;
+            // Pool launches and admin changes are not part of the LP activity report
+            if !matches!(
+                event,
+                MeteoraEvent::PoolCreated(_) | MeteoraEvent::AdminChange(_)
+            ) {
+                self.transaction_events.push(event);
             }
         }
 
+        self.remaining_instructions = self.remaining_instructions.saturating_sub(1);
+        if self.remaining_instructions == 0 {
+            self.flush_report().await;
+        }
+
         Ok(())
     }
 }
-fn check_accounts_in_client(fee_payer: Pubkey, account_keys: &[Pubkey]) -> bool {
-    let fee_payer_is_lp = LP_WALLETS
+/// All account keys of the transaction in message order: the static keys followed
+/// by the writable and readonly addresses loaded from address lookup tables.
+fn transaction_account_keys(
+    transaction_metadata: &TransactionMetadata,
+) -> Vec<(Pubkey, AccountKeySource)> {
+    let loaded_addresses = &transaction_metadata.meta.loaded_addresses;
+    let static_keys = transaction_metadata.message.static_account_keys();
+    let mut account_keys = Vec::with_capacity(
+        static_keys.len() + loaded_addresses.writable.len() + loaded_addresses.readonly.len(),
+    );
+    account_keys.extend(
+        static_keys
+            .iter()
+            .map(|key| (*key, AccountKeySource::Static)),
+    );
+    account_keys.extend(
+        loaded_addresses
+            .writable
+            .iter()
+            .map(|key| (*key, AccountKeySource::LookupTableWritable)),
+    );
+    account_keys.extend(
+        loaded_addresses
+            .readonly
+            .iter()
+            .map(|key| (*key, AccountKeySource::LookupTableReadonly)),
+    );
+    account_keys
+}
+
+/// Number of DLMM instructions in the transaction, top-level or reached through CPI,
+/// that decode and are therefore handed to the processor. Instructions unknown to
+/// the decoder never reach it, so they must not hold the report back.
+fn dlmm_instruction_count(transaction_metadata: &TransactionMetadata) -> usize {
+    let account_keys = transaction_account_keys(transaction_metadata);
+    let is_delivered = |program_id_index: u8, data: &[u8]| {
+        let is_dlmm = account_keys
+            .get(program_id_index as usize)
+            .is_some_and(|(program_id, _)| *program_id == PROGRAM_ID);
+        // The decoder only looks at the data, accounts are arranged afterwards
+        is_dlmm
+            && MeteoraDlmmDecoder
+                .decode_instruction(&Instruction {
+                    program_id: PROGRAM_ID,
+                    accounts: Vec::new(),
+                    data: data.to_vec(),
+                })
+                .is_some()
+    };
+    let top_level = transaction_metadata
+        .message
+        .instructions()
+        .iter()
+        .filter(|instruction| is_delivered(instruction.program_id_index, &instruction.data))
+        .count();
+    let inner = transaction_metadata
+        .meta
+        .inner_instructions
+        .iter()
+        .flatten()
+        .flat_map(|inner_instructions| &inner_instructions.instructions)
+        .filter(|inner| is_delivered(inner.instruction.program_id_index, &inner.instruction.data))
+        .count();
+    top_level + inner
+}
+
+/// Accounts of a top-level instruction rebuilt from the compiled instruction, so
+/// accounts loaded through lookup tables are always present. Inner instructions
+/// keep the accounts they were decoded with.
+fn resolve_instruction_accounts(
+    instruction_metadata: &InstructionMetadata,
+    account_keys: &[(Pubkey, AccountKeySource)],
+    decoded_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    if instruction_metadata.stack_height > 1 {
+        return decoded_accounts.to_vec();
+    }
+    let message = &instruction_metadata.transaction_metadata.message;
+    let Some(compiled) = message
+        .instructions()
+        .get(instruction_metadata.index as usize)
+    else {
+        return decoded_accounts.to_vec();
+    };
+
+    let resolved: Option<Vec<AccountMeta>> = compiled
+        .accounts
+        .iter()
+        .map(|index| account_meta_at(message, account_keys, *index as usize))
+        .collect();
+    resolved.unwrap_or_else(|| decoded_accounts.to_vec())
+}
+
+/// Account meta for the key at `index` of the full account key list
+fn account_meta_at(
+    message: &VersionedMessage,
+    account_keys: &[(Pubkey, AccountKeySource)],
+    index: usize,
+) -> Option<AccountMeta> {
+    let (pubkey, source) = *account_keys.get(index)?;
+    let header = message.header();
+    let signers = header.num_required_signatures as usize;
+    let static_len = message.static_account_keys().len();
+    let is_signer = index < signers;
+    let is_writable = match source {
+        AccountKeySource::Static if is_signer => {
+            index < signers - header.num_readonly_signed_accounts as usize
+        }
+        AccountKeySource::Static => {
+            index < static_len - header.num_readonly_unsigned_accounts as usize
+        }
+        AccountKeySource::LookupTableWritable => true,
+        AccountKeySource::LookupTableReadonly => false,
+    };
+    Some(AccountMeta {
+        pubkey,
+        is_signer,
+        is_writable,
+    })
+}
+
+fn check_accounts_in_client(
+    fee_payer: Pubkey,
+    account_keys: &[(Pubkey, AccountKeySource)],
+    lp_wallets: &[String],
+) -> bool {
+    let fee_payer_is_lp = lp_wallets
         .iter()
         .any(|wallet| wallet == &fee_payer.to_string());
 
-    // Check if any account_key is in LP_WALLETS
+    // Check if any account_key, static or loaded from a lookup table, is in lp_wallets
     let mut lp_account_keys = Vec::new();
-    for acc in account_keys {
-        if LP_WALLETS.iter().any(|wallet| wallet == &acc.to_string()) {
-            lp_account_keys.push(acc);
+    for (acc, source) in account_keys {
+        if lp_wallets.iter().any(|wallet| wallet == &acc.to_string()) {
+            lp_account_keys.push((acc, source));
         }
     }
 
@@ -240,11 +766,192 @@ fn check_accounts_in_client(fee_payer: Pubkey, account_keys: &[Pubkey]) -> bool
 
         if !lp_account_keys.is_empty() {
             info!("LP account keys:");
-            for acc in &lp_account_keys {
-                info!("  - {}", acc);
+            for (acc, source) in &lp_account_keys {
+                info!("  - {} ({})", acc, source);
             }
         }
         return true;
     }
     return false;
 }
+
+#[tokio::test]
+async fn test_report_after_undecodable_instruction() {
+    use {
+        carbon_core::deserialize::CarbonDeserialize,
+        carbon_meteora_dlmm_decoder::instructions::position_close_event::PositionCloseEvent,
+        solana_sdk::message::Message, solana_transaction_status::TransactionStatusMeta,
+        std::sync::Mutex,
+    };
+
+    #[derive(Default)]
+    struct ReportCapture(Mutex<Vec<TransactionReport>>);
+
+    #[async_trait]
+    impl EventSink for ReportCapture {
+        async fn handle(&self, _event: &MeteoraEvent) {}
+
+        async fn handle_report(&self, report: &TransactionReport) {
+            self.0.lock().unwrap().push(report.clone());
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "meteora-agent-state-report-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let state = Arc::new(StateStore::open(&path).unwrap());
+    let (position, owner, fee_payer) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let opened = PositionActivity::new(&Signature::new_unique(), 299, None, PositionEvent::Opened);
+    state.open_position(&position, &owner, &Pubkey::new_unique(), None, opened);
+
+    // A DLMM instruction the decoder does not know, then the close of the position
+    let mut close_data = PositionCloseEvent::DISCRIMINATOR.to_vec();
+    close_data.extend_from_slice(position.as_ref());
+    close_data.extend_from_slice(owner.as_ref());
+    let close = Instruction::new_with_bytes(PROGRAM_ID, &close_data, Vec::new());
+    let unknown = Instruction::new_with_bytes(PROGRAM_ID, &[0xff; 16], Vec::new());
+    let transaction_metadata = Arc::new(TransactionMetadata {
+        slot: 300,
+        signature: Signature::new_unique(),
+        fee_payer,
+        meta: TransactionStatusMeta::default(),
+        message: VersionedMessage::Legacy(Message::new(
+            &[unknown, close.clone()],
+            Some(&fee_payer),
+        )),
+        block_time: None,
+    });
+    assert_eq!(dlmm_instruction_count(&transaction_metadata), 1);
+
+    let sink = Arc::new(ReportCapture::default());
+    let mut processor = MeteoraInstructionProcessor::new()
+        .with_state(state)
+        .with_sink(sink.clone());
+    let instruction_metadata = InstructionMetadata {
+        transaction_metadata: transaction_metadata.clone(),
+        stack_height: 1,
+        index: 1,
+    };
+    let decoded_instruction = MeteoraDlmmDecoder.decode_instruction(&close).unwrap();
+    processor
+        .process(
+            (
+                instruction_metadata,
+                decoded_instruction,
+                NestedInstructions::default(),
+            ),
+            Arc::new(MetricsCollection::new(Vec::new())),
+        )
+        .await
+        .unwrap();
+
+    // Reported right away rather than when the next transaction shows up
+    let reports = sink.0.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(
+        reports[0].signature,
+        transaction_metadata.signature.to_string()
+    );
+    assert!(matches!(
+        reports[0].events[..],
+        [MeteoraEvent::PositionClosed(_)]
+    ));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_lookup_table_accounts() {
+    use {
+        solana_sdk::{
+            hash::Hash,
+            instruction::CompiledInstruction,
+            message::{
+                MessageHeader,
+                v0::{self, LoadedAddresses, MessageAddressTableLookup},
+            },
+        },
+        solana_transaction_status::TransactionStatusMeta,
+    };
+
+    // Static keys: the fee payer and the program. The position and the LP wallet
+    // come from the writable half of a lookup table, the event authority from the
+    // readonly half.
+    let (fee_payer, position, lp_wallet, event_authority) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let message = VersionedMessage::V0(v0::Message {
+        header: MessageHeader {
+            num_required_signatures: 1,
+            num_readonly_signed_accounts: 0,
+            num_readonly_unsigned_accounts: 1,
+        },
+        account_keys: vec![fee_payer, PROGRAM_ID],
+        recent_blockhash: Hash::default(),
+        instructions: vec![CompiledInstruction::new_from_raw_parts(
+            1,
+            Vec::new(),
+            vec![3, 2, 4, 0],
+        )],
+        address_table_lookups: vec![MessageAddressTableLookup {
+            account_key: Pubkey::new_unique(),
+            writable_indexes: vec![0, 1],
+            readonly_indexes: vec![0],
+        }],
+    });
+    let meta = TransactionStatusMeta {
+        loaded_addresses: LoadedAddresses {
+            writable: vec![position, lp_wallet],
+            readonly: vec![event_authority],
+        },
+        ..TransactionStatusMeta::default()
+    };
+    let instruction_metadata = InstructionMetadata {
+        transaction_metadata: Arc::new(TransactionMetadata {
+            slot: 300,
+            signature: Signature::new_unique(),
+            fee_payer,
+            meta,
+            message,
+            block_time: None,
+        }),
+        stack_height: 1,
+        index: 0,
+    };
+
+    let account_keys = transaction_account_keys(&instruction_metadata.transaction_metadata);
+    assert_eq!(
+        account_keys[3],
+        (lp_wallet, AccountKeySource::LookupTableWritable)
+    );
+    let accounts = resolve_instruction_accounts(&instruction_metadata, &account_keys, &[]);
+    assert_eq!(
+        accounts,
+        vec![
+            AccountMeta::new(lp_wallet, false),
+            AccountMeta::new(position, false),
+            AccountMeta::new_readonly(event_authority, false),
+            AccountMeta::new(fee_payer, true),
+        ]
+    );
+
+    // The LP wallet is only reachable through the lookup table
+    assert!(check_accounts_in_client(
+        fee_payer,
+        &account_keys,
+        &[lp_wallet.to_string()]
+    ));
+    assert!(!check_accounts_in_client(
+        fee_payer,
+        &account_keys[..2],
+        &[lp_wallet.to_string()]
+    ));
+}
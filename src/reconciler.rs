@@ -0,0 +1,360 @@
+use async_trait::async_trait;
+use log::{error, info, warn};
+use solana_client::client_error::Result as ClientResult;
+use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash, signature::Signature};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+use crate::{message::TelegramService, rpc_pool::RpcPool, state::StateStore};
+
+/// How often pending transactions are checked at finalized commitment
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(10);
+/// Maximum number of signatures per `getSignatureStatuses` request
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+/// Where the reconciler reads transaction statuses from, the `RpcPool` outside tests
+#[async_trait]
+pub trait StatusSource: Send + Sync {
+    /// Statuses of `signatures`, `None` for those unknown to the ledger
+    async fn statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ClientResult<Vec<Option<TransactionStatus>>>;
+
+    /// Whether a transaction using `blockhash` can still land
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> ClientResult<bool>;
+
+    /// Oldest slot the ledger history reaches back to
+    async fn minimum_ledger_slot(&self) -> ClientResult<u64>;
+}
+
+#[async_trait]
+impl StatusSource for RpcPool {
+    async fn statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ClientResult<Vec<Option<TransactionStatus>>> {
+        // With history, so transactions older than the node's status cache are found too
+        self.call(|rpc_client| async move {
+            rpc_client
+                .get_signature_statuses_with_history(signatures)
+                .await
+        })
+        .await
+        .map(|response| response.value)
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> ClientResult<bool> {
+        self.call(|rpc_client| async move {
+            rpc_client
+                .is_blockhash_valid(blockhash, CommitmentConfig::confirmed())
+                .await
+        })
+        .await
+    }
+
+    async fn minimum_ledger_slot(&self) -> ClientResult<u64> {
+        self.call(|rpc_client| async move { rpc_client.minimum_ledger_slot().await })
+            .await
+    }
+}
+
+/// Transaction processed at confirmed commitment, waiting to be finalized
+struct PendingTransaction {
+    slot: u64,
+    /// Once it expires the transaction can no longer land
+    recent_blockhash: Hash,
+    /// Alerts sent for this transaction, repeated in the correction if it is dropped
+    alerts: Vec<String>,
+}
+
+/// Checks transactions processed at `confirmed` until they are finalized, and
+/// sends a correction and rolls state back for those that never make it.
+///
+/// A transaction that landed but failed stays as it was processed. One missing
+/// from the ledger is only rolled back once its blockhash expired and the ledger
+/// history reaches back to its slot, anything less proves nothing.
+pub struct Reconciler<S = RpcPool> {
+    source: Arc<S>,
+    state: Arc<StateStore>,
+    telegram_service: Option<Arc<TelegramService>>,
+    pending: Mutex<HashMap<Signature, PendingTransaction>>,
+}
+
+impl<S: StatusSource> Reconciler<S> {
+    pub fn new(
+        source: Arc<S>,
+        state: Arc<StateStore>,
+        telegram_service: Option<Arc<TelegramService>>,
+    ) -> Self {
+        Self {
+            source,
+            state,
+            telegram_service,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start watching a transaction processed at confirmed commitment
+    pub fn track(&self, signature: Signature, slot: u64, recent_blockhash: Hash) {
+        self.pending
+            .lock()
+            .unwrap()
+            .entry(signature)
+            .or_insert_with(|| PendingTransaction {
+                slot,
+                recent_blockhash,
+                alerts: Vec::new(),
+            });
+    }
+
+    /// Remember an alert sent for a watched transaction
+    pub fn note_alert(&self, signature: &Signature, alert: &str) {
+        if let Some(pending) = self.pending.lock().unwrap().get_mut(signature) {
+            pending.alerts.push(alert.to_string());
+        }
+    }
+
+    /// Reconcile pending transactions periodically
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()>
+    where
+        S: 'static,
+    {
+        let reconciler = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(RECONCILE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                reconciler.reconcile().await;
+            }
+        })
+    }
+
+    /// Check every pending transaction, oldest first so the finalized checkpoint is
+    /// up to date before a newer transaction is rolled back to it
+    async fn reconcile(&self) {
+        let mut signatures: Vec<(u64, Signature)> = self
+            .pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(signature, pending)| (pending.slot, *signature))
+            .collect();
+        signatures.sort();
+        let signatures: Vec<Signature> = signatures
+            .into_iter()
+            .map(|(_, signature)| signature)
+            .collect();
+
+        for chunk in signatures.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            let statuses = match self.source.statuses(chunk).await {
+                Ok(statuses) => statuses,
+                Err(e) => {
+                    warn!("Failed to fetch signature statuses: {}", e);
+                    continue;
+                }
+            };
+
+            for (signature, status) in chunk.iter().zip(statuses) {
+                match status {
+                    // Landed for good, what was processed stands even if it failed
+                    Some(status)
+                        if matches!(
+                            status.confirmation_status,
+                            Some(TransactionConfirmationStatus::Finalized)
+                        ) =>
+                    {
+                        let Some(pending) = self.pending.lock().unwrap().remove(signature) else {
+                            continue;
+                        };
+                        if let Some(err) = status.err {
+                            info!("Transaction {} was finalized as failed: {}", signature, err);
+                        }
+                        self.state.record_finalized(signature, pending.slot);
+                    }
+                    // Still on its way to finalized
+                    Some(_) => {}
+                    None => self.check_dropped(signature).await,
+                }
+            }
+        }
+    }
+
+    /// Roll back a transaction unknown to the ledger once it can no longer land.
+    ///
+    /// It is given up on without a rollback when the ledger history does not reach
+    /// back to its slot, since its absence then proves nothing.
+    async fn check_dropped(&self, signature: &Signature) {
+        let Some((slot, blockhash)) = self
+            .pending
+            .lock()
+            .unwrap()
+            .get(signature)
+            .map(|pending| (pending.slot, pending.recent_blockhash))
+        else {
+            return;
+        };
+        match self.source.is_blockhash_valid(&blockhash).await {
+            Ok(false) => {}
+            // It may still land
+            Ok(true) => return,
+            Err(e) => {
+                warn!("Failed to check the blockhash of {}: {}", signature, e);
+                return;
+            }
+        }
+        match self.source.minimum_ledger_slot().await {
+            Ok(minimum_slot) if minimum_slot <= slot => {}
+            Ok(minimum_slot) => {
+                warn!(
+                    "Cannot tell whether transaction {} at slot {} was dropped, RPC history starts at slot {}; keeping it",
+                    signature, slot, minimum_slot
+                );
+                self.pending.lock().unwrap().remove(signature);
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to fetch the RPC history range: {}", e);
+                return;
+            }
+        }
+
+        let pending = self.pending.lock().unwrap().remove(signature);
+        if let Some(pending) = pending {
+            self.rollback(signature, pending).await;
+        }
+    }
+
+    /// Undo a transaction that never landed
+    async fn rollback(&self, signature: &Signature, pending: PendingTransaction) {
+        warn!(
+            "Transaction {} at slot {} was dropped or forked out, rolling back",
+            signature, pending.slot
+        );
+        self.state.revert_transaction(signature);
+
+        let Some(telegram_service) = &self.telegram_service else {
+            return;
+        };
+        if pending.alerts.is_empty() {
+            return;
+        }
+        let message = format!(
+            "Correction: transaction {} at slot {} was dropped or forked out and never finalized. Disregard the alert below:\n\n{}",
+            signature,
+            pending.slot,
+            pending.alerts.join("\n\n")
+        );
+        if let Err(e) = telegram_service.send_message(&message).await {
+            error!("Failed to send correction for {}: {}", signature, e);
+        } else {
+            info!("Sent correction for {}", signature);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_reconcile() {
+    use {
+        solana_sdk::{pubkey::Pubkey, transaction::TransactionError},
+        std::collections::HashSet,
+    };
+
+    /// Ledger with fixed statuses, valid blockhashes and history range
+    struct StubSource {
+        statuses: HashMap<Signature, TransactionStatus>,
+        valid_blockhashes: HashSet<Hash>,
+        minimum_ledger_slot: u64,
+    }
+
+    #[async_trait]
+    impl StatusSource for StubSource {
+        async fn statuses(
+            &self,
+            signatures: &[Signature],
+        ) -> ClientResult<Vec<Option<TransactionStatus>>> {
+            Ok(signatures
+                .iter()
+                .map(|signature| self.statuses.get(signature).cloned())
+                .collect())
+        }
+
+        async fn is_blockhash_valid(&self, blockhash: &Hash) -> ClientResult<bool> {
+            Ok(self.valid_blockhashes.contains(blockhash))
+        }
+
+        async fn minimum_ledger_slot(&self) -> ClientResult<u64> {
+            Ok(self.minimum_ledger_slot)
+        }
+    }
+
+    let finalized_status = |slot, err: Option<TransactionError>| TransactionStatus {
+        slot,
+        confirmations: None,
+        status: err.clone().map_or(Ok(()), Err),
+        err,
+        confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+    };
+    let [pruned, finalized, failed, recent, dropped] = [(); 5].map(|_| Signature::new_unique());
+    let (expired_blockhash, recent_blockhash) = (Hash::new_unique(), Hash::new_unique());
+    let source = Arc::new(StubSource {
+        statuses: HashMap::from([
+            (finalized, finalized_status(100, None)),
+            (
+                failed,
+                finalized_status(150, Some(TransactionError::AccountInUse)),
+            ),
+        ]),
+        valid_blockhashes: HashSet::from([recent_blockhash]),
+        minimum_ledger_slot: 80,
+    });
+
+    let path = std::env::temp_dir().join(format!(
+        "meteora-agent-state-reconcile-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let state = Arc::new(StateStore::open(&path).unwrap());
+    let (position, owner, mint) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let reconciler = Reconciler::new(source, state.clone(), None);
+    for (signature, slot, blockhash, amount) in [
+        (pruned, 50, expired_blockhash, 1),
+        (finalized, 100, expired_blockhash, 10),
+        (failed, 150, expired_blockhash, 100),
+        (recent, 190, recent_blockhash, 1_000),
+        (dropped, 200, expired_blockhash, 10_000),
+    ] {
+        state.record_processed(&signature, slot);
+        state.record_claim(&signature, &position, &owner, None, &[(mint, amount)]);
+        reconciler.track(signature, slot, blockhash);
+    }
+    reconciler.reconcile().await;
+
+    // Finalized transactions, failed or not, stand and the dropped one rolls the
+    // checkpoint back to the newest of them. One older than the RPC history and
+    // one that may still land are not rolled back.
+    let pending: Vec<Signature> = reconciler.pending.lock().unwrap().keys().copied().collect();
+    assert_eq!(pending, vec![recent]);
+    assert_eq!(state.checkpoint().unwrap().signature(), Some(failed));
+    let (_, wallet_totals) = state
+        .record_claim(
+            &Signature::new_unique(),
+            &position,
+            &owner,
+            None,
+            &[(mint, 0)],
+        )
+        .unwrap();
+    assert_eq!(wallet_totals.fees[&mint.to_string()], 1_111);
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,1366 @@
+use {
+    carbon_core::{
+        deserialize::ArrangeAccounts,
+        instruction::{InstructionDecoder, NestedInstructions},
+    },
+    carbon_meteora_dlmm_decoder::{
+        MeteoraDlmmDecoder, PROGRAM_ID as METEORA_PROGRAM_ID,
+        instructions::{
+            MeteoraDlmmInstruction, add_liquidity::AddLiquidity,
+            add_liquidity_by_strategy::AddLiquidityByStrategy,
+            add_liquidity_by_strategy_one_side::AddLiquidityByStrategyOneSide,
+            add_liquidity_by_weight::AddLiquidityByWeight,
+            add_liquidity_one_side::AddLiquidityOneSide,
+            add_liquidity_one_side_precise::AddLiquidityOneSidePrecise, claim_fee::ClaimFee,
+            claim_reward::ClaimReward, close_position::ClosePosition,
+            initialize_customizable_permissionless_lb_pair::InitializeCustomizablePermissionlessLbPair,
+            initialize_lb_pair::InitializeLbPair,
+            initialize_permission_lb_pair::InitializePermissionLbPair,
+            initialize_position::InitializePosition,
+            initialize_position_pda::InitializePositionPda,
+            remove_all_liquidity::RemoveAllLiquidity, remove_liquidity::RemoveLiquidity,
+            remove_liquidity_by_range::RemoveLiquidityByRange, swap::Swap,
+            swap_exact_out::SwapExactOut, swap_with_price_impact::SwapWithPriceImpact,
+        },
+        types::{StrategyParameters, StrategyType},
+    },
+    serde::Serialize,
+    solana_sdk::{instruction::AccountMeta, pubkey::Pubkey},
+    std::fmt,
+};
+
+use crate::{
+    account_processer::base_fee_bps,
+    token::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+};
+
+/// Basis points removing all the liquidity of a bin
+const FULL_BPS: u16 = 10_000;
+
+/// SPL token transfer made through a CPI of a DLMM instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenTransfer {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    /// Only known for `TransferChecked`
+    pub mint: Option<Pubkey>,
+    pub amount: u64,
+}
+
+/// Parse a `Transfer` or `TransferChecked` instruction of the SPL Token or Token-2022 program
+fn parse_token_transfer(
+    program_id: &Pubkey,
+    accounts: &[AccountMeta],
+    data: &[u8],
+) -> Option<TokenTransfer> {
+    if *program_id != TOKEN_PROGRAM_ID && *program_id != TOKEN_2022_PROGRAM_ID {
+        return None;
+    }
+    let amount = u64::from_le_bytes(data.get(1..9)?.try_into().ok()?);
+    match data.first()? {
+        // Transfer: source, destination, authority
+        3 => Some(TokenTransfer {
+            source: accounts.first()?.pubkey,
+            destination: accounts.get(1)?.pubkey,
+            mint: None,
+            amount,
+        }),
+        // TransferChecked: source, mint, destination, authority
+        12 => Some(TokenTransfer {
+            source: accounts.first()?.pubkey,
+            destination: accounts.get(2)?.pubkey,
+            mint: Some(accounts.get(1)?.pubkey),
+            amount,
+        }),
+        _ => None,
+    }
+}
+
+/// Token transfers made by the inner instructions, in execution order
+pub fn token_transfers(nested_instructions: &NestedInstructions) -> Vec<TokenTransfer> {
+    let mut transfers = Vec::new();
+    for nested in nested_instructions.iter() {
+        let instruction = &nested.instruction;
+        if let Some(transfer) = parse_token_transfer(
+            &instruction.program_id,
+            &instruction.accounts,
+            &instruction.data,
+        ) {
+            transfers.push(transfer);
+        }
+        transfers.extend(token_transfers(&nested.inner_instructions));
+    }
+    transfers
+}
+
+/// Total amount transferred out of `reserve`
+fn withdrawn_from(transfers: &[TokenTransfer], reserve: &Pubkey) -> u64 {
+    transfers
+        .iter()
+        .filter(|transfer| transfer.source == *reserve)
+        .map(|transfer| transfer.amount)
+        .sum()
+}
+
+/// Total amount transferred into `reserve`
+fn deposited_to(transfers: &[TokenTransfer], reserve: &Pubkey) -> u64 {
+    transfers
+        .iter()
+        .filter(|transfer| transfer.destination == *reserve)
+        .map(|transfer| transfer.amount)
+        .sum()
+}
+
+/// First event emitted through a self-CPI of the instruction that `pick` accepts
+fn find_event<T>(
+    nested_instructions: &NestedInstructions,
+    pick: &impl Fn(MeteoraDlmmInstruction) -> Option<T>,
+) -> Option<T> {
+    nested_instructions.iter().find_map(|nested| {
+        let event = (nested.instruction.program_id == METEORA_PROGRAM_ID)
+            .then(|| MeteoraDlmmDecoder.decode_instruction(&nested.instruction))
+            .flatten()
+            .and_then(|decoded| pick(decoded.data));
+        event.or_else(|| find_event(&nested.inner_instructions, pick))
+    })
+}
+
+/// Shape the program spreads liquidity with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StrategyShape {
+    Spot,
+    Curve,
+    BidAsk,
+}
+
+/// Which tokens a strategy deposit is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StrategySide {
+    OneSide,
+    Balanced,
+    Imbalanced,
+}
+
+/// How the deposited amounts are spread over the bin range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum LiquidityStrategy {
+    /// Per-bin shares of the amounts chosen by the caller
+    Distribution,
+    /// Per-bin weights chosen by the caller
+    Weights,
+    /// Exact per-bin amounts chosen by the caller
+    PreciseAmounts,
+    /// Shape applied by the program, with its raw parameters (trailing zeros trimmed)
+    Strategy {
+        shape: StrategyShape,
+        side: StrategySide,
+        parameters: Vec<u8>,
+    },
+}
+
+impl fmt::Display for LiquidityStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiquidityStrategy::Distribution => write!(f, "custom distribution"),
+            LiquidityStrategy::Weights => write!(f, "custom weights"),
+            LiquidityStrategy::PreciseAmounts => write!(f, "precise amounts"),
+            LiquidityStrategy::Strategy {
+                shape,
+                side,
+                parameters,
+            } => {
+                write!(f, "{:?} ({:?})", shape, side)?;
+                if !parameters.is_empty() {
+                    write!(f, " parameters {:?}", parameters)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&StrategyParameters> for LiquidityStrategy {
+    fn from(parameters: &StrategyParameters) -> Self {
+        let (shape, side) = match parameters.strategy_type {
+            StrategyType::SpotOneSide => (StrategyShape::Spot, StrategySide::OneSide),
+            StrategyType::CurveOneSide => (StrategyShape::Curve, StrategySide::OneSide),
+            StrategyType::BidAskOneSide => (StrategyShape::BidAsk, StrategySide::OneSide),
+            StrategyType::SpotBalanced => (StrategyShape::Spot, StrategySide::Balanced),
+            StrategyType::CurveBalanced => (StrategyShape::Curve, StrategySide::Balanced),
+            StrategyType::BidAskBalanced => (StrategyShape::BidAsk, StrategySide::Balanced),
+            StrategyType::SpotImBalanced => (StrategyShape::Spot, StrategySide::Imbalanced),
+            StrategyType::CurveImBalanced => (StrategyShape::Curve, StrategySide::Imbalanced),
+            StrategyType::BidAskImBalanced => (StrategyShape::BidAsk, StrategySide::Imbalanced),
+        };
+        let raw = &parameters.parameteres;
+        let used = raw
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |last| last + 1);
+        LiquidityStrategy::Strategy {
+            shape,
+            side,
+            parameters: raw[..used].to_vec(),
+        }
+    }
+}
+
+/// Liquidity deposited into a position by any of the add-liquidity instructions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidityAdded {
+    pub instruction: &'static str,
+    pub lb_pair: Pubkey,
+    pub position: Pubkey,
+    pub sender: Pubkey,
+    /// Mint of token X, `None` for one-sided deposits of token Y
+    pub token_x_mint: Option<Pubkey>,
+    /// Mint of token Y, `None` for one-sided deposits of token X
+    pub token_y_mint: Option<Pubkey>,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lower_bin_id: i32,
+    pub upper_bin_id: i32,
+    /// Active bin expected by the caller, for instructions carrying slippage protection
+    pub active_id: Option<i32>,
+    pub strategy: LiquidityStrategy,
+}
+
+/// Lowest and highest bin of a deposit
+fn bin_range(bin_ids: impl Iterator<Item = i32>) -> Option<(i32, i32)> {
+    bin_ids.fold(None, |range, bin_id| match range {
+        None => Some((bin_id, bin_id)),
+        Some((lower, upper)) => Some((lower.min(bin_id), upper.max(bin_id))),
+    })
+}
+
+/// A one-sided deposit holds token Y if its mint is the Y mint of the pool. When the
+/// pool is unknown its bins tell instead: bins above the active one can only hold
+/// token X, so a deposit ending at or below the active bin holds token Y.
+fn is_one_side_y(
+    token_mint: &Pubkey,
+    pool_mints: Option<(Pubkey, Pubkey)>,
+    upper_bin_id: i32,
+    active_id: Option<i32>,
+) -> bool {
+    match pool_mints {
+        Some((_, token_y_mint)) => *token_mint == token_y_mint,
+        None => active_id.is_some_and(|active_id| upper_bin_id <= active_id),
+    }
+}
+
+/// Total of compressed per-bin amounts, saturating instead of overflowing
+fn decompressed_amount(amounts: impl Iterator<Item = u32>, multiplier: u64) -> u64 {
+    amounts.fold(0u64, |total, amount| {
+        total.saturating_add((amount as u64).saturating_mul(multiplier))
+    })
+}
+
+/// Build the record of a one-sided deposit of `amount` of `token_mint`, on the Y
+/// side when `side_y` is set
+#[allow(clippy::too_many_arguments)]
+fn one_side_deposit(
+    instruction: &'static str,
+    lb_pair: Pubkey,
+    position: Pubkey,
+    sender: Pubkey,
+    token_mint: Pubkey,
+    amount: u64,
+    (lower_bin_id, upper_bin_id): (i32, i32),
+    active_id: Option<i32>,
+    side_y: bool,
+    strategy: LiquidityStrategy,
+) -> LiquidityAdded {
+    LiquidityAdded {
+        instruction,
+        lb_pair,
+        position,
+        sender,
+        token_x_mint: (!side_y).then_some(token_mint),
+        token_y_mint: side_y.then_some(token_mint),
+        amount_x: if side_y { 0 } else { amount },
+        amount_y: if side_y { amount } else { 0 },
+        lower_bin_id,
+        upper_bin_id,
+        active_id,
+        strategy,
+    }
+}
+
+/// Pool of a one-sided deposit, whose mints tell the side of the deposit
+pub fn one_side_lb_pair(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+) -> Option<Pubkey> {
+    match instruction {
+        MeteoraDlmmInstruction::AddLiquidityByStrategyOneSide(_) => {
+            AddLiquidityByStrategyOneSide::arrange_accounts(accounts).map(|a| a.lb_pair)
+        }
+        MeteoraDlmmInstruction::AddLiquidityOneSide(_) => {
+            AddLiquidityOneSide::arrange_accounts(accounts).map(|a| a.lb_pair)
+        }
+        MeteoraDlmmInstruction::AddLiquidityOneSidePrecise(_) => {
+            AddLiquidityOneSidePrecise::arrange_accounts(accounts).map(|a| a.lb_pair)
+        }
+        _ => None,
+    }
+}
+
+/// Decode any add-liquidity instruction into a `LiquidityAdded` record.
+///
+/// `pool_mints` are the X and Y mints of the pool when they are known, which tell
+/// the side of a one-sided deposit. Without them the side is guessed from the bins,
+/// and an `AddLiquidityOneSidePrecise` deposit, which carries no active bin, is
+/// taken as token X. Returns `None` for other instructions or when the accounts
+/// cannot be arranged.
+pub fn decode_liquidity_added(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+    pool_mints: Option<(Pubkey, Pubkey)>,
+) -> Option<LiquidityAdded> {
+    match instruction {
+        MeteoraDlmmInstruction::AddLiquidity(ix) => {
+            let accounts = AddLiquidity::arrange_accounts(accounts)?;
+            let parameter = &ix.liquidity_parameter;
+            let (lower_bin_id, upper_bin_id) =
+                bin_range(parameter.bin_liquidity_dist.iter().map(|bin| bin.bin_id))?;
+            Some(LiquidityAdded {
+                instruction: "AddLiquidity",
+                lb_pair: accounts.lb_pair,
+                position: accounts.position,
+                sender: accounts.sender,
+                token_x_mint: Some(accounts.token_x_mint),
+                token_y_mint: Some(accounts.token_y_mint),
+                amount_x: parameter.amount_x,
+                amount_y: parameter.amount_y,
+                lower_bin_id,
+                upper_bin_id,
+                active_id: None,
+                strategy: LiquidityStrategy::Distribution,
+            })
+        }
+        MeteoraDlmmInstruction::AddLiquidityByWeight(ix) => {
+            let accounts = AddLiquidityByWeight::arrange_accounts(accounts)?;
+            let parameter = &ix.liquidity_parameter;
+            let (lower_bin_id, upper_bin_id) =
+                bin_range(parameter.bin_liquidity_dist.iter().map(|bin| bin.bin_id))?;
+            Some(LiquidityAdded {
+                instruction: "AddLiquidityByWeight",
+                lb_pair: accounts.lb_pair,
+                position: accounts.position,
+                sender: accounts.sender,
+                token_x_mint: Some(accounts.token_x_mint),
+                token_y_mint: Some(accounts.token_y_mint),
+                amount_x: parameter.amount_x,
+                amount_y: parameter.amount_y,
+                lower_bin_id,
+                upper_bin_id,
+                active_id: Some(parameter.active_id),
+                strategy: LiquidityStrategy::Weights,
+            })
+        }
+        MeteoraDlmmInstruction::AddLiquidityByStrategy(ix) => {
+            let accounts = AddLiquidityByStrategy::arrange_accounts(accounts)?;
+            let parameter = &ix.liquidity_parameter;
+            Some(LiquidityAdded {
+                instruction: "AddLiquidityByStrategy",
+                lb_pair: accounts.lb_pair,
+                position: accounts.position,
+                sender: accounts.sender,
+                token_x_mint: Some(accounts.token_x_mint),
+                token_y_mint: Some(accounts.token_y_mint),
+                amount_x: parameter.amount_x,
+                amount_y: parameter.amount_y,
+                lower_bin_id: parameter.strategy_parameters.min_bin_id,
+                upper_bin_id: parameter.strategy_parameters.max_bin_id,
+                active_id: Some(parameter.active_id),
+                strategy: (&parameter.strategy_parameters).into(),
+            })
+        }
+        MeteoraDlmmInstruction::AddLiquidityByStrategyOneSide(ix) => {
+            let accounts = AddLiquidityByStrategyOneSide::arrange_accounts(accounts)?;
+            let parameter = &ix.liquidity_parameter;
+            Some(one_side_deposit(
+                "AddLiquidityByStrategyOneSide",
+                accounts.lb_pair,
+                accounts.position,
+                accounts.sender,
+                accounts.token_mint,
+                parameter.amount,
+                (
+                    parameter.strategy_parameters.min_bin_id,
+                    parameter.strategy_parameters.max_bin_id,
+                ),
+                Some(parameter.active_id),
+                is_one_side_y(
+                    &accounts.token_mint,
+                    pool_mints,
+                    parameter.strategy_parameters.max_bin_id,
+                    Some(parameter.active_id),
+                ),
+                (&parameter.strategy_parameters).into(),
+            ))
+        }
+        MeteoraDlmmInstruction::AddLiquidityOneSide(ix) => {
+            let accounts = AddLiquidityOneSide::arrange_accounts(accounts)?;
+            let parameter = &ix.liquidity_parameter;
+            let range = bin_range(parameter.bin_liquidity_dist.iter().map(|bin| bin.bin_id))?;
+            Some(one_side_deposit(
+                "AddLiquidityOneSide",
+                accounts.lb_pair,
+                accounts.position,
+                accounts.sender,
+                accounts.token_mint,
+                parameter.amount,
+                range,
+                Some(parameter.active_id),
+                is_one_side_y(
+                    &accounts.token_mint,
+                    pool_mints,
+                    range.1,
+                    Some(parameter.active_id),
+                ),
+                LiquidityStrategy::Weights,
+            ))
+        }
+        MeteoraDlmmInstruction::AddLiquidityOneSidePrecise(ix) => {
+            let accounts = AddLiquidityOneSidePrecise::arrange_accounts(accounts)?;
+            let parameter = &ix.parameter;
+            let amount = decompressed_amount(
+                parameter.bins.iter().map(|bin| bin.amount),
+                parameter.decompress_multiplier,
+            );
+            let range = bin_range(parameter.bins.iter().map(|bin| bin.bin_id))?;
+            Some(one_side_deposit(
+                "AddLiquidityOneSidePrecise",
+                accounts.lb_pair,
+                accounts.position,
+                accounts.sender,
+                accounts.token_mint,
+                amount,
+                range,
+                None,
+                is_one_side_y(&accounts.token_mint, pool_mints, range.1, None),
+                LiquidityStrategy::PreciseAmounts,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Share of the liquidity of one bin taken out of a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BinRemoval {
+    pub bin_id: i32,
+    pub bps: u16,
+}
+
+/// Liquidity withdrawn from a position by any of the removal instructions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidityRemoved {
+    pub instruction: &'static str,
+    pub lb_pair: Pubkey,
+    pub position: Pubkey,
+    pub sender: Pubkey,
+    /// Mints are not part of `ClosePosition`, which withdraws nothing
+    pub token_x_mint: Option<Pubkey>,
+    pub token_y_mint: Option<Pubkey>,
+    /// Bin range removed from, `None` for `RemoveAllLiquidity` when the position bounds are unknown
+    pub lower_bin_id: Option<i32>,
+    pub upper_bin_id: Option<i32>,
+    /// Basis points removed per bin, empty when the whole position is withdrawn
+    pub bins: Vec<BinRemoval>,
+    /// Whether no liquidity is left in the position afterwards
+    pub fully_exited: bool,
+    /// Amounts actually withdrawn, taken from the inner token transfers
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Whether removing `bins` leaves nothing in a position spanning `position_range`
+fn removes_everything(bins: &[BinRemoval], position_range: Option<(i32, i32)>) -> bool {
+    let Some((lower, upper)) = position_range else {
+        return false;
+    };
+    (lower..=upper).all(|bin_id| {
+        bins.iter()
+            .any(|bin| bin.bin_id == bin_id && bin.bps >= FULL_BPS)
+    })
+}
+
+/// Decode any liquidity removal instruction into a `LiquidityRemoved` record.
+///
+/// `position_range` is the bin range of the position when it is known, used to
+/// tell whether a partial removal actually emptied it. Returns `None` for other
+/// instructions, for an empty bin range or when the accounts cannot be arranged.
+pub fn decode_liquidity_removed(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+    nested_instructions: &NestedInstructions,
+    position_range: Option<(i32, i32)>,
+) -> Option<LiquidityRemoved> {
+    let (instruction_name, bins) = match instruction {
+        MeteoraDlmmInstruction::RemoveLiquidity(ix) => (
+            "RemoveLiquidity",
+            ix.bin_liquidity_removal
+                .iter()
+                .map(|bin| BinRemoval {
+                    bin_id: bin.bin_id,
+                    bps: bin.bps_to_remove,
+                })
+                .collect(),
+        ),
+        // An inverted range removes nothing, it must not read as a full exit
+        MeteoraDlmmInstruction::RemoveLiquidityByRange(ix) if ix.from_bin_id > ix.to_bin_id => {
+            return None;
+        }
+        MeteoraDlmmInstruction::RemoveLiquidityByRange(ix) => (
+            "RemoveLiquidityByRange",
+            (ix.from_bin_id..=ix.to_bin_id)
+                .map(|bin_id| BinRemoval {
+                    bin_id,
+                    bps: ix.bps_to_remove,
+                })
+                .collect(),
+        ),
+        MeteoraDlmmInstruction::RemoveAllLiquidity(_) => ("RemoveAllLiquidity", Vec::new()),
+        MeteoraDlmmInstruction::ClosePosition(_) => {
+            let accounts = ClosePosition::arrange_accounts(accounts)?;
+            return Some(LiquidityRemoved {
+                instruction: "ClosePosition",
+                lb_pair: accounts.lb_pair,
+                position: accounts.position,
+                sender: accounts.sender,
+                token_x_mint: None,
+                token_y_mint: None,
+                lower_bin_id: position_range.map(|(lower, _)| lower),
+                upper_bin_id: position_range.map(|(_, upper)| upper),
+                bins: Vec::new(),
+                fully_exited: true,
+                amount_x: 0,
+                amount_y: 0,
+            });
+        }
+        _ => return None,
+    };
+
+    // The three removal instructions share the same account layout
+    let accounts = match instruction {
+        MeteoraDlmmInstruction::RemoveAllLiquidity(_) => {
+            let accounts = RemoveAllLiquidity::arrange_accounts(accounts)?;
+            (
+                accounts.lb_pair,
+                accounts.position,
+                accounts.sender,
+                accounts.token_x_mint,
+                accounts.token_y_mint,
+                accounts.reserve_x,
+                accounts.reserve_y,
+            )
+        }
+        MeteoraDlmmInstruction::RemoveLiquidityByRange(_) => {
+            let accounts = RemoveLiquidityByRange::arrange_accounts(accounts)?;
+            (
+                accounts.lb_pair,
+                accounts.position,
+                accounts.sender,
+                accounts.token_x_mint,
+                accounts.token_y_mint,
+                accounts.reserve_x,
+                accounts.reserve_y,
+            )
+        }
+        _ => {
+            let accounts = RemoveLiquidity::arrange_accounts(accounts)?;
+            (
+                accounts.lb_pair,
+                accounts.position,
+                accounts.sender,
+                accounts.token_x_mint,
+                accounts.token_y_mint,
+                accounts.reserve_x,
+                accounts.reserve_y,
+            )
+        }
+    };
+    let (lb_pair, position, sender, token_x_mint, token_y_mint, reserve_x, reserve_y) = accounts;
+
+    let transfers = token_transfers(nested_instructions);
+    let (lower_bin_id, upper_bin_id) = match bin_range(bins.iter().map(|bin| bin.bin_id)) {
+        Some((lower, upper)) => (Some(lower), Some(upper)),
+        None => (
+            position_range.map(|(lower, _)| lower),
+            position_range.map(|(_, upper)| upper),
+        ),
+    };
+    let fully_exited = bins.is_empty() || removes_everything(&bins, position_range);
+
+    Some(LiquidityRemoved {
+        instruction: instruction_name,
+        lb_pair,
+        position,
+        sender,
+        token_x_mint: Some(token_x_mint),
+        token_y_mint: Some(token_y_mint),
+        lower_bin_id,
+        upper_bin_id,
+        bins,
+        fully_exited,
+        amount_x: withdrawn_from(&transfers, &reserve_x),
+        amount_y: withdrawn_from(&transfers, &reserve_y),
+    })
+}
+
+/// Swap as executed on chain, as opposed to the bounds requested by the user
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapExecuted {
+    pub instruction: &'static str,
+    pub lb_pair: Pubkey,
+    pub user: Pubkey,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    /// Token X sold for token Y, or the other way around
+    pub swap_for_y: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Fee figures and bins are only known when the `SwapEvent` is available
+    pub fee: Option<u64>,
+    pub protocol_fee: Option<u64>,
+    pub start_bin_id: Option<i32>,
+    pub end_bin_id: Option<i32>,
+}
+
+impl SwapExecuted {
+    /// Mints of the tokens sold and bought
+    pub fn mints_in_out(&self) -> (Pubkey, Pubkey) {
+        if self.swap_for_y {
+            (self.token_x_mint, self.token_y_mint)
+        } else {
+            (self.token_y_mint, self.token_x_mint)
+        }
+    }
+}
+
+/// Decode any swap instruction into a `SwapExecuted` record with the realized
+/// amounts, from its `SwapEvent` or else from the transfers in and out of the reserves.
+///
+/// Returns `None` for other instructions, when the accounts cannot be arranged
+/// or when neither source is available.
+pub fn decode_swap_executed(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+    nested_instructions: &NestedInstructions,
+) -> Option<SwapExecuted> {
+    // The swap instructions share the same account layout
+    let (instruction_name, lb_pair, user, token_x_mint, token_y_mint, reserve_x, reserve_y) =
+        match instruction {
+            MeteoraDlmmInstruction::Swap(_) => {
+                let accounts = Swap::arrange_accounts(accounts)?;
+                (
+                    "Swap",
+                    accounts.lb_pair,
+                    accounts.user,
+                    accounts.token_x_mint,
+                    accounts.token_y_mint,
+                    accounts.reserve_x,
+                    accounts.reserve_y,
+                )
+            }
+            MeteoraDlmmInstruction::SwapExactOut(_) => {
+                let accounts = SwapExactOut::arrange_accounts(accounts)?;
+                (
+                    "SwapExactOut",
+                    accounts.lb_pair,
+                    accounts.user,
+                    accounts.token_x_mint,
+                    accounts.token_y_mint,
+                    accounts.reserve_x,
+                    accounts.reserve_y,
+                )
+            }
+            MeteoraDlmmInstruction::SwapWithPriceImpact(_) => {
+                let accounts = SwapWithPriceImpact::arrange_accounts(accounts)?;
+                (
+                    "SwapWithPriceImpact",
+                    accounts.lb_pair,
+                    accounts.user,
+                    accounts.token_x_mint,
+                    accounts.token_y_mint,
+                    accounts.reserve_x,
+                    accounts.reserve_y,
+                )
+            }
+            _ => return None,
+        };
+
+    let record = SwapExecuted {
+        instruction: instruction_name,
+        lb_pair,
+        user,
+        token_x_mint,
+        token_y_mint,
+        swap_for_y: true,
+        amount_in: 0,
+        amount_out: 0,
+        fee: None,
+        protocol_fee: None,
+        start_bin_id: None,
+        end_bin_id: None,
+    };
+    let swap_event = find_event(nested_instructions, &|instruction| match instruction {
+        MeteoraDlmmInstruction::SwapEvent(event) => Some(event),
+        _ => None,
+    });
+    if let Some(event) = swap_event {
+        return Some(SwapExecuted {
+            swap_for_y: event.swap_for_y,
+            amount_in: event.amount_in,
+            amount_out: event.amount_out,
+            fee: Some(event.fee),
+            protocol_fee: Some(event.protocol_fee),
+            start_bin_id: Some(event.start_bin_id),
+            end_bin_id: Some(event.end_bin_id),
+            ..record
+        });
+    }
+
+    let transfers = token_transfers(nested_instructions);
+    let into_x = deposited_to(&transfers, &reserve_x);
+    let into_y = deposited_to(&transfers, &reserve_y);
+    if into_x == 0 && into_y == 0 {
+        return None;
+    }
+    let swap_for_y = into_x > 0;
+    Some(SwapExecuted {
+        swap_for_y,
+        amount_in: if swap_for_y { into_x } else { into_y },
+        amount_out: if swap_for_y {
+            withdrawn_from(&transfers, &reserve_y)
+        } else {
+            withdrawn_from(&transfers, &reserve_x)
+        },
+        ..record
+    })
+}
+
+/// Swap fees or farming rewards harvested from a position
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Claimed {
+    pub instruction: &'static str,
+    pub lb_pair: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    /// Reward slot of the pool, `None` for swap fees
+    pub reward_index: Option<u64>,
+    /// Amount harvested per token mint
+    pub amounts: Vec<(Pubkey, u64)>,
+}
+
+/// Decode a `ClaimFee` or `ClaimReward` instruction into a `Claimed` record, with
+/// the amounts from its `ClaimFeeEvent` / `ClaimRewardEvent` or else from the
+/// transfers out of the reserves or reward vault.
+///
+/// Returns `None` for other instructions or when the accounts cannot be arranged.
+pub fn decode_claimed(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+    nested_instructions: &NestedInstructions,
+) -> Option<Claimed> {
+    match instruction {
+        MeteoraDlmmInstruction::ClaimFee(_) => {
+            let accounts = ClaimFee::arrange_accounts(accounts)?;
+            let event = find_event(nested_instructions, &|instruction| match instruction {
+                MeteoraDlmmInstruction::ClaimFeeEvent(event) => Some(event),
+                _ => None,
+            });
+            let (owner, fee_x, fee_y) = match event {
+                Some(event) => (event.owner, event.fee_x, event.fee_y),
+                None => {
+                    let transfers = token_transfers(nested_instructions);
+                    (
+                        accounts.sender,
+                        withdrawn_from(&transfers, &accounts.reserve_x),
+                        withdrawn_from(&transfers, &accounts.reserve_y),
+                    )
+                }
+            };
+            Some(Claimed {
+                instruction: "ClaimFee",
+                lb_pair: accounts.lb_pair,
+                position: accounts.position,
+                owner,
+                reward_index: None,
+                amounts: vec![
+                    (accounts.token_x_mint, fee_x),
+                    (accounts.token_y_mint, fee_y),
+                ],
+            })
+        }
+        MeteoraDlmmInstruction::ClaimReward(ix) => {
+            let accounts = ClaimReward::arrange_accounts(accounts)?;
+            let event = find_event(nested_instructions, &|instruction| match instruction {
+                MeteoraDlmmInstruction::ClaimRewardEvent(event) => Some(event),
+                _ => None,
+            });
+            let (owner, amount) = match event {
+                Some(event) => (event.owner, event.total_reward),
+                None => (
+                    accounts.sender,
+                    withdrawn_from(
+                        &token_transfers(nested_instructions),
+                        &accounts.reward_vault,
+                    ),
+                ),
+            };
+            Some(Claimed {
+                instruction: "ClaimReward",
+                lb_pair: accounts.lb_pair,
+                position: accounts.position,
+                owner,
+                reward_index: Some(ix.reward_index),
+                amounts: vec![(accounts.reward_mint, amount)],
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Position created for an owner
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionOpened {
+    pub instruction: &'static str,
+    pub position: Pubkey,
+    pub lb_pair: Pubkey,
+    pub owner: Pubkey,
+    /// Lowest and highest bin, not part of `PositionCreateEvent`
+    pub bin_range: Option<(i32, i32)>,
+}
+
+/// Decode `InitializePosition`, `InitializePositionPda` or `PositionCreateEvent`
+/// into a `PositionOpened` record.
+///
+/// Returns `None` for other instructions or when the accounts cannot be arranged.
+pub fn decode_position_opened(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+) -> Option<PositionOpened> {
+    // A position spans `width` bins starting at `lower_bin_id`
+    let bin_range = |lower_bin_id: i32, width: i32| (lower_bin_id, lower_bin_id + width - 1);
+    match instruction {
+        MeteoraDlmmInstruction::InitializePosition(ix) => {
+            let accounts = InitializePosition::arrange_accounts(accounts)?;
+            Some(PositionOpened {
+                instruction: "InitializePosition",
+                position: accounts.position,
+                lb_pair: accounts.lb_pair,
+                owner: accounts.owner,
+                bin_range: Some(bin_range(ix.lower_bin_id, ix.width)),
+            })
+        }
+        MeteoraDlmmInstruction::InitializePositionPda(ix) => {
+            let accounts = InitializePositionPda::arrange_accounts(accounts)?;
+            Some(PositionOpened {
+                instruction: "InitializePositionPda",
+                position: accounts.position,
+                lb_pair: accounts.lb_pair,
+                owner: accounts.owner,
+                bin_range: Some(bin_range(ix.lower_bin_id, ix.width)),
+            })
+        }
+        MeteoraDlmmInstruction::PositionCreateEvent(event) => Some(PositionOpened {
+            instruction: "PositionCreateEvent",
+            position: event.position,
+            lb_pair: event.lb_pair,
+            owner: event.owner,
+            bin_range: None,
+        }),
+        _ => None,
+    }
+}
+
+/// New pool created on the DLMM program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolCreated {
+    pub instruction: &'static str,
+    pub lb_pair: Pubkey,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub bin_step: u16,
+    /// Initial active bin, not part of `LbPairCreateEvent`
+    pub active_id: Option<i32>,
+    /// Base fee, only known from the instruction for permissioned and customizable pools
+    pub base_fee_bps: Option<f64>,
+    /// Slot or timestamp trading opens at, when set at creation
+    pub activation_point: Option<u64>,
+}
+
+impl PoolCreated {
+    /// Initial price of token X in token Y, in base units (not adjusted for decimals)
+    pub fn initial_price(&self) -> Option<f64> {
+        let active_id = self.active_id?;
+        Some((1.0 + self.bin_step as f64 / 10_000.0).powi(active_id))
+    }
+}
+
+/// Decode any pool creation instruction or `LbPairCreateEvent` into a `PoolCreated` record.
+///
+/// Returns `None` for other instructions or when the accounts cannot be arranged.
+pub fn decode_pool_created(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+) -> Option<PoolCreated> {
+    match instruction {
+        MeteoraDlmmInstruction::InitializeLbPair(ix) => {
+            let accounts = InitializeLbPair::arrange_accounts(accounts)?;
+            Some(PoolCreated {
+                instruction: "InitializeLbPair",
+                lb_pair: accounts.lb_pair,
+                token_x_mint: accounts.token_mint_x,
+                token_y_mint: accounts.token_mint_y,
+                bin_step: ix.bin_step,
+                active_id: Some(ix.active_id),
+                // Fee parameters come from the preset parameter account
+                base_fee_bps: None,
+                activation_point: None,
+            })
+        }
+        MeteoraDlmmInstruction::InitializePermissionLbPair(ix) => {
+            let accounts = InitializePermissionLbPair::arrange_accounts(accounts)?;
+            let parameters = &ix.ix_data;
+            Some(PoolCreated {
+                instruction: "InitializePermissionLbPair",
+                lb_pair: accounts.lb_pair,
+                token_x_mint: accounts.token_mint_x,
+                token_y_mint: accounts.token_mint_y,
+                bin_step: parameters.bin_step,
+                active_id: Some(parameters.active_id),
+                base_fee_bps: Some(base_fee_bps(
+                    parameters.base_factor,
+                    parameters.bin_step,
+                    parameters.base_fee_power_factor,
+                )),
+                activation_point: None,
+            })
+        }
+        MeteoraDlmmInstruction::InitializeCustomizablePermissionlessLbPair(ix) => {
+            let accounts = InitializeCustomizablePermissionlessLbPair::arrange_accounts(accounts)?;
+            let parameters = &ix.params;
+            Some(PoolCreated {
+                instruction: "InitializeCustomizablePermissionlessLbPair",
+                lb_pair: accounts.lb_pair,
+                token_x_mint: accounts.token_mint_x,
+                token_y_mint: accounts.token_mint_y,
+                bin_step: parameters.bin_step,
+                active_id: Some(parameters.active_id),
+                base_fee_bps: Some(base_fee_bps(
+                    parameters.base_factor,
+                    parameters.bin_step,
+                    parameters.base_fee_power_factor,
+                )),
+                activation_point: parameters.activation_point,
+            })
+        }
+        MeteoraDlmmInstruction::LbPairCreateEvent(event) => Some(PoolCreated {
+            instruction: "LbPairCreateEvent",
+            lb_pair: event.lb_pair,
+            token_x_mint: event.token_x,
+            token_y_mint: event.token_y,
+            bin_step: event.bin_step,
+            active_id: None,
+            base_fee_bps: None,
+            activation_point: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Change made by a pool admin, operator or reward funder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AdminChange {
+    pub instruction: &'static str,
+    pub lb_pair: Pubkey,
+    /// Instruction or event arguments, as JSON
+    pub details: String,
+}
+
+/// Decode admin and governance instructions and events into an `AdminChange` record:
+/// fee parameter updates, pair status toggles, protocol fee withdrawals, reward
+/// initialization, funding and updates, and activation point changes.
+///
+/// Returns `None` for other instructions. All these instructions take the pool as
+/// their first account.
+pub fn decode_admin_change(
+    instruction: &MeteoraDlmmInstruction,
+    accounts: &[AccountMeta],
+) -> Option<AdminChange> {
+    let lb_pair = accounts.first().map(|account| account.pubkey);
+    let (instruction_name, lb_pair, details) = match instruction {
+        MeteoraDlmmInstruction::UpdateFeeParameters(ix) => {
+            ("UpdateFeeParameters", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::TogglePairStatus(ix) => {
+            ("TogglePairStatus", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::WithdrawProtocolFee(ix) => {
+            ("WithdrawProtocolFee", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::InitializeReward(ix) => {
+            ("InitializeReward", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::FundReward(ix) => {
+            ("FundReward", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::UpdateRewardFunder(ix) => {
+            ("UpdateRewardFunder", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::UpdateRewardDuration(ix) => {
+            ("UpdateRewardDuration", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::SetActivationPoint(ix) => {
+            ("SetActivationPoint", lb_pair?, serde_json::to_string(ix))
+        }
+        MeteoraDlmmInstruction::FeeParameterUpdateEvent(event) => (
+            "FeeParameterUpdateEvent",
+            event.lb_pair,
+            serde_json::to_string(event),
+        ),
+        _ => return None,
+    };
+    Some(AdminChange {
+        instruction: instruction_name,
+        lb_pair,
+        details: details.unwrap_or_else(|_| "json decode error".to_string()),
+    })
+}
+
+#[test]
+fn test_liquidity_ranges() {
+    assert_eq!(bin_range([5, -3, 12, 0].into_iter()), Some((-3, 12)));
+    assert_eq!(bin_range(std::iter::empty()), None);
+
+    // The pool mints tell the side of a one-sided deposit, whatever its bins
+    let (token_x_mint, token_y_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let pool_mints = Some((token_x_mint, token_y_mint));
+    assert!(is_one_side_y(&token_y_mint, pool_mints, 100, Some(100)));
+    assert!(is_one_side_y(&token_y_mint, pool_mints, 101, None));
+    assert!(!is_one_side_y(&token_x_mint, pool_mints, 99, Some(100)));
+
+    // Without them, bins up to the active one can only hold token Y
+    assert!(is_one_side_y(&token_y_mint, None, 99, Some(100)));
+    assert!(is_one_side_y(&token_y_mint, None, 100, Some(100)));
+    assert!(!is_one_side_y(&token_y_mint, None, 101, Some(100)));
+    assert!(!is_one_side_y(&token_y_mint, None, 99, None));
+}
+
+#[test]
+fn test_removes_everything() {
+    // A removal only empties the position if every one of its bins goes at 100%
+    let bins = |bps| {
+        (10..=12)
+            .map(|bin_id| BinRemoval { bin_id, bps })
+            .collect::<Vec<_>>()
+    };
+    assert!(removes_everything(&bins(FULL_BPS), Some((10, 12))));
+    assert!(!removes_everything(&bins(5_000), Some((10, 12))));
+    assert!(!removes_everything(&bins(FULL_BPS), Some((10, 13))));
+    assert!(!removes_everything(&bins(FULL_BPS), None));
+}
+
+#[test]
+fn test_token_transfers() {
+    // Transfer and TransferChecked of either token program, nothing else
+    let account = |pubkey| AccountMeta::new(pubkey, false);
+    let (source, mint, destination) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let mut data = vec![3];
+    data.extend_from_slice(&42u64.to_le_bytes());
+    let transfer = parse_token_transfer(
+        &TOKEN_PROGRAM_ID,
+        &[account(source), account(destination)],
+        &data,
+    )
+    .unwrap();
+    assert_eq!((transfer.destination, transfer.amount), (destination, 42));
+    data[0] = 12;
+    data.push(6);
+    let transfer = parse_token_transfer(
+        &TOKEN_2022_PROGRAM_ID,
+        &[account(source), account(mint), account(destination)],
+        &data,
+    )
+    .unwrap();
+    assert_eq!(transfer.mint, Some(mint));
+    assert_eq!(transfer.destination, destination);
+    assert!(parse_token_transfer(&METEORA_PROGRAM_ID, &[account(source)], &data).is_none());
+}
+
+#[test]
+fn test_precise_one_side() {
+    assert_eq!(decompressed_amount([1, 2, 3].into_iter(), 1_000), 6_000);
+    assert_eq!(
+        decompressed_amount([u32::MAX, 1].into_iter(), u64::MAX),
+        u64::MAX
+    );
+}
+
+#[test]
+fn test_inverted_range_removal() {
+    let removal = MeteoraDlmmInstruction::RemoveLiquidityByRange(RemoveLiquidityByRange {
+        from_bin_id: 12,
+        to_bin_id: 10,
+        bps_to_remove: FULL_BPS,
+    });
+    assert!(
+        decode_liquidity_removed(&removal, &[], &NestedInstructions::default(), None).is_none()
+    );
+}
+
+#[test]
+fn test_admin_change_pool() {
+    // Admin instructions name their pool through the first account
+    let (lb_pair, admin) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let toggle = MeteoraDlmmInstruction::TogglePairStatus(
+        carbon_meteora_dlmm_decoder::instructions::toggle_pair_status::TogglePairStatus {},
+    );
+    let accounts = [
+        AccountMeta::new(lb_pair, false),
+        AccountMeta::new_readonly(admin, true),
+    ];
+    let admin_change = decode_admin_change(&toggle, &accounts).unwrap();
+    assert_eq!(admin_change.instruction, "TogglePairStatus");
+    assert_eq!(admin_change.lb_pair, lb_pair);
+    assert!(decode_admin_change(&toggle, &[]).is_none());
+}
+
+#[test]
+fn test_swap_executed() {
+    use {
+        carbon_core::{
+            deserialize::CarbonDeserialize,
+            instruction::{InstructionMetadata, NestedInstruction},
+            transaction::TransactionMetadata,
+        },
+        carbon_meteora_dlmm_decoder::instructions::swap_event::SwapEvent,
+        solana_sdk::{instruction::Instruction, message::VersionedMessage, signature::Signature},
+        solana_transaction_status::TransactionStatusMeta,
+        std::sync::Arc,
+    };
+
+    let metadata = InstructionMetadata {
+        transaction_metadata: Arc::new(TransactionMetadata {
+            slot: 300,
+            signature: Signature::new_unique(),
+            fee_payer: Pubkey::new_unique(),
+            meta: TransactionStatusMeta::default(),
+            message: VersionedMessage::default(),
+            block_time: None,
+        }),
+        stack_height: 2,
+        index: 0,
+    };
+    let nested = |instructions: Vec<Instruction>| {
+        NestedInstructions(
+            instructions
+                .into_iter()
+                .map(|instruction| NestedInstruction {
+                    metadata: metadata.clone(),
+                    instruction,
+                    inner_instructions: NestedInstructions::default(),
+                })
+                .collect(),
+        )
+    };
+    let transfer = |source: Pubkey, destination: Pubkey, amount: u64| {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction::new_with_bytes(
+            TOKEN_PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), true),
+            ],
+        )
+    };
+
+    // lb_pair, bitmap extension, reserve X / Y, user token in / out, mint X / Y,
+    // oracle, host fee, user, token programs, event authority, program
+    let accounts: Vec<AccountMeta> = (0..15)
+        .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+        .collect();
+    let key = |index: usize| accounts[index].pubkey;
+    let (lb_pair, reserve_x, reserve_y, user_token_in, user_token_out) =
+        (key(0), key(2), key(3), key(4), key(5));
+    let (token_x_mint, token_y_mint, user) = (key(6), key(7), key(10));
+    let swap = MeteoraDlmmInstruction::Swap(Swap {
+        amount_in: 1_000,
+        min_amount_out: 1,
+    });
+
+    // The SwapEvent wins over the transfers, with the fee figures and bins
+    let mut event_data = SwapEvent::DISCRIMINATOR.to_vec();
+    event_data.extend_from_slice(lb_pair.as_ref());
+    event_data.extend_from_slice(user.as_ref());
+    event_data.extend_from_slice(&5i32.to_le_bytes());
+    event_data.extend_from_slice(&7i32.to_le_bytes());
+    event_data.extend_from_slice(&1_000u64.to_le_bytes());
+    event_data.extend_from_slice(&480u64.to_le_bytes());
+    event_data.push(1);
+    event_data.extend_from_slice(&3u64.to_le_bytes());
+    event_data.extend_from_slice(&1u64.to_le_bytes());
+    event_data.extend_from_slice(&25u128.to_le_bytes());
+    event_data.extend_from_slice(&0u64.to_le_bytes());
+    let event = Instruction::new_with_bytes(METEORA_PROGRAM_ID, &event_data, Vec::new());
+    let swapped = decode_swap_executed(
+        &swap,
+        &accounts,
+        &nested(vec![
+            transfer(user_token_in, reserve_x, 1_000),
+            transfer(reserve_y, user_token_out, 1),
+            event,
+        ]),
+    )
+    .unwrap();
+    assert_eq!(swapped.instruction, "Swap");
+    assert_eq!((swapped.lb_pair, swapped.user), (lb_pair, user));
+    assert!(swapped.swap_for_y);
+    assert_eq!((swapped.amount_in, swapped.amount_out), (1_000, 480));
+    assert_eq!((swapped.fee, swapped.protocol_fee), (Some(3), Some(1)));
+    assert_eq!(
+        (swapped.start_bin_id, swapped.end_bin_id),
+        (Some(5), Some(7))
+    );
+
+    // Without the event, the transfers in and out of the reserves tell the direction
+    // and the amounts
+    let swapped = decode_swap_executed(
+        &swap,
+        &accounts,
+        &nested(vec![
+            transfer(user_token_in, reserve_y, 700),
+            transfer(reserve_x, user_token_out, 300),
+        ]),
+    )
+    .unwrap();
+    assert!(!swapped.swap_for_y);
+    assert_eq!((swapped.amount_in, swapped.amount_out), (700, 300));
+    assert_eq!(swapped.mints_in_out(), (token_y_mint, token_x_mint));
+    assert_eq!((swapped.fee, swapped.start_bin_id), (None, None));
+
+    // Nothing to go by
+    assert!(decode_swap_executed(&swap, &accounts, &NestedInstructions::default()).is_none());
+    assert!(decode_swap_executed(&swap, &accounts[..3], &nested(Vec::new())).is_none());
+}
+
+#[test]
+fn test_claimed() {
+    use {
+        carbon_core::{
+            deserialize::CarbonDeserialize,
+            instruction::{InstructionMetadata, NestedInstruction},
+            transaction::TransactionMetadata,
+        },
+        carbon_meteora_dlmm_decoder::instructions::claim_fee_event::ClaimFeeEvent,
+        solana_sdk::{instruction::Instruction, message::VersionedMessage, signature::Signature},
+        solana_transaction_status::TransactionStatusMeta,
+        std::sync::Arc,
+    };
+
+    let metadata = InstructionMetadata {
+        transaction_metadata: Arc::new(TransactionMetadata {
+            slot: 300,
+            signature: Signature::new_unique(),
+            fee_payer: Pubkey::new_unique(),
+            meta: TransactionStatusMeta::default(),
+            message: VersionedMessage::default(),
+            block_time: None,
+        }),
+        stack_height: 2,
+        index: 0,
+    };
+    let nested = |instructions: Vec<Instruction>| {
+        NestedInstructions(
+            instructions
+                .into_iter()
+                .map(|instruction| NestedInstruction {
+                    metadata: metadata.clone(),
+                    instruction,
+                    inner_instructions: NestedInstructions::default(),
+                })
+                .collect(),
+        )
+    };
+    let transfer = |source: Pubkey, amount: u64| {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction::new_with_bytes(
+            TOKEN_PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), true),
+            ],
+        )
+    };
+    let accounts = |count| -> Vec<AccountMeta> {
+        (0..count)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect()
+    };
+
+    // lb_pair, position, bin arrays, sender, reserve X / Y, user tokens X / Y,
+    // mint X / Y, token program, event authority, program
+    let fee_accounts = accounts(14);
+    let key = |index: usize| fee_accounts[index].pubkey;
+    let (position, sender, reserve_x, reserve_y) = (key(1), key(4), key(5), key(6));
+    let (token_x_mint, token_y_mint) = (key(9), key(10));
+    let claim_fee = MeteoraDlmmInstruction::ClaimFee(ClaimFee {});
+
+    // The ClaimFeeEvent gives the fees of both tokens and the position owner
+    let owner = Pubkey::new_unique();
+    let mut event_data = ClaimFeeEvent::DISCRIMINATOR.to_vec();
+    event_data.extend_from_slice(key(0).as_ref());
+    event_data.extend_from_slice(position.as_ref());
+    event_data.extend_from_slice(owner.as_ref());
+    event_data.extend_from_slice(&10u64.to_le_bytes());
+    event_data.extend_from_slice(&20u64.to_le_bytes());
+    let event = Instruction::new_with_bytes(METEORA_PROGRAM_ID, &event_data, Vec::new());
+    let claimed = decode_claimed(&claim_fee, &fee_accounts, &nested(vec![event])).unwrap();
+    assert_eq!((claimed.position, claimed.owner), (position, owner));
+    assert_eq!(claimed.reward_index, None);
+    assert_eq!(
+        claimed.amounts,
+        vec![(token_x_mint, 10), (token_y_mint, 20)]
+    );
+
+    // Without it, the transfers out of each reserve, claimed by the sender
+    let claimed = decode_claimed(
+        &claim_fee,
+        &fee_accounts,
+        &nested(vec![transfer(reserve_x, 7), transfer(reserve_y, 9)]),
+    )
+    .unwrap();
+    assert_eq!(claimed.owner, sender);
+    assert_eq!(claimed.amounts, vec![(token_x_mint, 7), (token_y_mint, 9)]);
+
+    // lb_pair, position, bin arrays, sender, reward vault, reward mint, user token,
+    // token program, event authority, program
+    let reward_accounts = accounts(11);
+    let (reward_vault, reward_mint) = (reward_accounts[5].pubkey, reward_accounts[6].pubkey);
+    let claim_reward = MeteoraDlmmInstruction::ClaimReward(ClaimReward { reward_index: 1 });
+    let claimed = decode_claimed(
+        &claim_reward,
+        &reward_accounts,
+        &nested(vec![transfer(reward_vault, 42), transfer(reserve_x, 5)]),
+    )
+    .unwrap();
+    assert_eq!(claimed.instruction, "ClaimReward");
+    assert_eq!(claimed.reward_index, Some(1));
+    assert_eq!(claimed.amounts, vec![(reward_mint, 42)]);
+
+    assert!(decode_claimed(&claim_fee, &fee_accounts[..4], &nested(Vec::new())).is_none());
+}
@@ -0,0 +1,246 @@
+use {
+    anyhow::{Context, Result},
+    async_trait::async_trait,
+    carbon_core::{
+        datasource::{Datasource, Update, UpdateType},
+        error::{CarbonResult, Error},
+        metrics::MetricsCollection,
+    },
+    carbon_meteora_dlmm_decoder::PROGRAM_ID as METEORA_PROGRAM_ID,
+    log::{info, warn},
+    solana_sdk::{commitment_config::CommitmentConfig, signature::Signature},
+    solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta,
+    std::{fs::OpenOptions, io::Write, path::PathBuf, str::FromStr, sync::Arc, time::Duration},
+    tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        sync::mpsc::Sender,
+    },
+    tokio_util::sync::CancellationToken,
+};
+
+use crate::{
+    cli::RecordArgs,
+    datasource::{
+        fetch_encoded_transaction, fetch_new_signatures, transaction_update_from_encoded,
+    },
+    rpc_pool::RpcPool,
+    state::Checkpoint,
+};
+
+/// Datasource replaying transactions recorded as JSONL, one `getTransaction`
+/// result (base64 encoded) per line, then shutting the pipeline down.
+pub struct ReplayDatasource {
+    files: Vec<PathBuf>,
+    original_timing: bool,
+}
+
+impl ReplayDatasource {
+    pub fn new(files: Vec<PathBuf>, original_timing: bool) -> Self {
+        Self {
+            files,
+            original_timing,
+        }
+    }
+
+    async fn replay_file(
+        &self,
+        file: &PathBuf,
+        sender: &Sender<Update>,
+        cancellation_token: &CancellationToken,
+        previous_block_time: &mut Option<i64>,
+    ) -> CarbonResult<usize> {
+        let reader = tokio::fs::File::open(file)
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to open {}: {}", file.display(), e)))?;
+        let mut lines = BufReader::new(reader).lines();
+        let mut replayed = 0;
+
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to read {}: {}", file.display(), e)))?
+        {
+            if cancellation_token.is_cancelled() {
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let encoded_transaction =
+                match serde_json::from_str::<EncodedConfirmedTransactionWithStatusMeta>(&line) {
+                    Ok(encoded_transaction) => encoded_transaction,
+                    Err(e) => {
+                        warn!("Skipping malformed line in {}: {}", file.display(), e);
+                        continue;
+                    }
+                };
+            let Some(update) = transaction_update_from_encoded(encoded_transaction)? else {
+                continue;
+            };
+
+            if self.original_timing {
+                if let (Some(previous), Some(current)) = (*previous_block_time, update.block_time) {
+                    if current > previous {
+                        tokio::time::sleep(Duration::from_secs((current - previous) as u64)).await;
+                    }
+                }
+                *previous_block_time = update.block_time.or(*previous_block_time);
+            }
+
+            if sender
+                .send(Update::Transaction(Box::new(update)))
+                .await
+                .is_err()
+            {
+                break;
+            }
+            replayed += 1;
+        }
+
+        Ok(replayed)
+    }
+}
+
+#[async_trait]
+impl Datasource for ReplayDatasource {
+    async fn consume(
+        &self,
+        sender: &Sender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let mut previous_block_time = None;
+        for file in &self.files {
+            let replayed = self
+                .replay_file(file, sender, &cancellation_token, &mut previous_block_time)
+                .await?;
+            info!("Replayed {} transactions from {}", replayed, file.display());
+        }
+
+        // Nothing else will arrive, let the pipeline drain and stop
+        cancellation_token.cancel();
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+/// Append live transactions of an address to a JSONL fixture file, in the
+/// format read by `ReplayDatasource`.
+pub async fn record(rpc_pool: Arc<RpcPool>, args: RecordArgs) -> Result<()> {
+    let address = args.address.unwrap_or(METEORA_PROGRAM_ID);
+    let commitment = CommitmentConfig::finalized();
+    let mut out = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&args.out)
+        .with_context(|| format!("Failed to open {}", args.out.display()))?;
+    info!(
+        "Recording transactions of {} into {}",
+        address,
+        args.out.display()
+    );
+
+    let mut until: Option<Checkpoint> = None;
+    let mut recorded = 0;
+    loop {
+        // Everything since the previous poll, however many pages, oldest first so
+        // the fixture keeps chain order
+        let statuses =
+            fetch_new_signatures(&*rpc_pool, &address, until.as_ref(), 100, commitment).await?;
+        if let Some(newest) = statuses.last() {
+            until = Some(Checkpoint {
+                signature: newest.signature.clone(),
+                slot: newest.slot,
+            });
+        }
+
+        for status in statuses.iter().filter(|status| status.err.is_none()) {
+            let signature = Signature::from_str(&status.signature)?;
+            let encoded_transaction = match rpc_pool
+                .call(|rpc_client| async move {
+                    fetch_encoded_transaction(&rpc_client, signature, commitment).await
+                })
+                .await
+            {
+                Ok(encoded_transaction) => encoded_transaction,
+                Err(e) => {
+                    warn!("Skipping {}: {}", signature, e);
+                    continue;
+                }
+            };
+            writeln!(out, "{}", serde_json::to_string(&encoded_transaction)?)?;
+            recorded += 1;
+            if args.limit.is_some_and(|limit| recorded >= limit) {
+                info!("Recorded {} transactions", recorded);
+                return Ok(());
+            }
+        }
+        out.flush()?;
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    info!("Recorded {} transactions", recorded);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_fixture() {
+    let fixture = PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/replay.jsonl"
+    ));
+    let datasource = ReplayDatasource::new(vec![fixture], false);
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+    let cancellation_token = CancellationToken::new();
+    datasource
+        .consume(
+            &sender,
+            cancellation_token.clone(),
+            Arc::new(MetricsCollection::new(vec![])),
+        )
+        .await
+        .unwrap();
+    drop(sender);
+
+    let mut updates = Vec::new();
+    while let Some(Update::Transaction(update)) = receiver.recv().await {
+        updates.push(update);
+    }
+    // Transactions come in file order, and the pipeline is told to stop afterwards
+    let replayed: Vec<(String, u64, Option<i64>)> = updates
+        .iter()
+        .map(|update| (update.signature.to_string(), update.slot, update.block_time))
+        .collect();
+    assert_eq!(
+        replayed,
+        vec![
+            (
+                "3hizm34taS8t9UvpJg9oRCJ7EWYkuUHNCecrhuBZjG7L2RfqEqgApn2VsKS94Agj9UgBdgQT6HsaaFRUu7ZT44sU"
+                    .to_string(),
+                300_000_000,
+                Some(1_730_000_000)
+            ),
+            (
+                "2soASZVz6NaEUZtRyCbf3hAdpPAAiecRovUSi99FFw9GJGQTbdoPFaFctNx1Nzt2FzPMLj5JjBnkXJm6CGofULNX"
+                    .to_string(),
+                300_000_004,
+                Some(1_730_000_002)
+            ),
+        ]
+    );
+    assert!(
+        updates[0]
+            .transaction
+            .message
+            .static_account_keys()
+            .contains(&METEORA_PROGRAM_ID)
+    );
+    assert!(cancellation_token.is_cancelled());
+}
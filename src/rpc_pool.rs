@@ -0,0 +1,376 @@
+use anyhow::{Result, bail};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+
+use crate::utils::{CONFIG, RpcEndpointConfig, SOLANA_RPC, SOLANA_WS};
+
+/// An endpoint is unhealthy once it falls this many slots behind the best one
+const MAX_SLOT_LAG: u64 = 50;
+/// An endpoint is unhealthy once more than this share of recent requests failed
+const MAX_ERROR_RATE: f64 = 0.5;
+/// An endpoint is unhealthy once its probe latency exceeds this
+const MAX_LATENCY: Duration = Duration::from_secs(5);
+
+/// Shared pool, set up once at startup by `RpcPool::init`
+static RPC_POOL: OnceCell<Arc<RpcPool>> = OnceCell::new();
+
+/// Error of a request made through the pool
+pub trait EndpointError: Display {
+    /// Whether the endpoint failed to serve the request, so another one may succeed.
+    /// Anything else, e.g. an invalid parameter or a missing transaction, would fail
+    /// the same way everywhere.
+    fn is_endpoint_failure(&self) -> bool;
+}
+
+impl EndpointError for ClientError {
+    /// Transport errors, rate limiting (HTTP 429) and server errors (HTTP 5xx)
+    fn is_endpoint_failure(&self) -> bool {
+        match self.kind() {
+            ClientErrorKind::Io(_) => true,
+            ClientErrorKind::Reqwest(e) => e
+                .status()
+                .is_none_or(|status| status.as_u16() == 429 || status.is_server_error()),
+            _ => false,
+        }
+    }
+}
+
+/// Health statistics of one endpoint
+#[derive(Debug)]
+struct EndpointHealth {
+    healthy: bool,
+    slot: u64,
+    latency: Duration,
+    requests: u32,
+    errors: u32,
+}
+
+impl EndpointHealth {
+    fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+}
+
+/// RPC endpoint with its weight, shared client and health statistics
+pub struct RpcEndpoint {
+    pub url: String,
+    pub weight: u32,
+    client: Arc<RpcClient>,
+    health: Mutex<EndpointHealth>,
+}
+
+impl RpcEndpoint {
+    fn new(url: String, weight: u32) -> Self {
+        Self {
+            client: Arc::new(RpcClient::new_with_commitment(
+                url.clone(),
+                CommitmentConfig::confirmed(),
+            )),
+            url,
+            weight: weight.max(1),
+            health: Mutex::new(EndpointHealth {
+                healthy: true,
+                slot: 0,
+                latency: Duration::ZERO,
+                requests: 0,
+                errors: 0,
+            }),
+        }
+    }
+
+    pub fn client(&self) -> Arc<RpcClient> {
+        self.client.clone()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health.lock().unwrap().healthy
+    }
+
+    /// Account the outcome of a request made through this endpoint
+    pub fn record(&self, success: bool) {
+        let mut health = self.health.lock().unwrap();
+        health.requests += 1;
+        if !success {
+            health.errors += 1;
+        }
+    }
+}
+
+/// Weighted set of RPC endpoints with periodic health probes and failover
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    cursor: AtomicUsize,
+}
+
+impl RpcPool {
+    /// Build a pool from endpoint configs, failing if none is configured
+    pub fn new(configs: Vec<RpcEndpointConfig>) -> Result<Self> {
+        let endpoints: Vec<RpcEndpoint> = configs
+            .into_iter()
+            .filter(|config| !config.url.trim().is_empty())
+            .map(|config| RpcEndpoint::new(config.url.trim().to_string(), config.weight))
+            .collect();
+        if endpoints.is_empty() {
+            bail!(
+                "No Solana RPC endpoint configured, set SOLANA_RPC or `rpc_endpoints` in config.json"
+            );
+        }
+        Ok(Self {
+            endpoints,
+            cursor: AtomicUsize::new(0),
+        })
+    }
+
+    /// Build the shared pool from `rpc_endpoints` in the config file and `SOLANA_RPC`
+    pub fn init() -> Result<Arc<RpcPool>> {
+        let mut configs = CONFIG.rpc_endpoints.clone();
+        configs.extend(SOLANA_RPC.split(',').map(|url| RpcEndpointConfig {
+            url: url.to_string(),
+            weight: 1,
+        }));
+        let pool = Arc::new(Self::new(configs)?);
+        for endpoint in &pool.endpoints {
+            info!("RPC endpoint {} (weight {})", endpoint.url, endpoint.weight);
+        }
+        Ok(RPC_POOL.get_or_init(|| pool).clone())
+    }
+
+    /// The shared pool
+    ///
+    /// # Panics
+    /// * If `RpcPool::init` has not been called yet.
+    pub fn global() -> Arc<RpcPool> {
+        RPC_POOL.get().expect("RPC pool is not initialized").clone()
+    }
+
+    /// The shared pool, `None` when running without RPC access, e.g. replaying fixtures
+    pub fn try_global() -> Option<Arc<RpcPool>> {
+        RPC_POOL.get().cloned()
+    }
+
+    /// Pick an endpoint by weighted round robin among the healthy ones, or the
+    /// least failing one if none is healthy.
+    pub fn select(&self) -> &RpcEndpoint {
+        let healthy: Vec<&RpcEndpoint> = self.endpoints.iter().filter(|e| e.is_healthy()).collect();
+        if healthy.is_empty() {
+            return self.least_failing();
+        }
+        let total_weight: u32 = healthy.iter().map(|e| e.weight).sum();
+        let mut ticket =
+            (self.cursor.fetch_add(1, Ordering::Relaxed) % total_weight as usize) as u32;
+        for endpoint in &healthy {
+            if ticket < endpoint.weight {
+                return endpoint;
+            }
+            ticket -= endpoint.weight;
+        }
+        healthy[0]
+    }
+
+    /// Endpoints in failover order: the selected one, then the remaining healthy
+    /// ones by weight, then the unhealthy ones.
+    pub fn ranked(&self) -> Vec<&RpcEndpoint> {
+        let selected = self.select();
+        let mut rest: Vec<&RpcEndpoint> = self
+            .endpoints
+            .iter()
+            .filter(|e| !std::ptr::eq(*e, selected))
+            .collect();
+        rest.sort_by_key(|e| (!e.is_healthy(), std::cmp::Reverse(e.weight)));
+        std::iter::once(selected).chain(rest).collect()
+    }
+
+    /// Websocket URL for subscriptions, `SOLANA_WS` or derived from the heaviest endpoint
+    pub fn websocket_url(&self) -> String {
+        SOLANA_WS.clone().unwrap_or_else(|| {
+            let primary = self.endpoints.iter().max_by_key(|e| e.weight).unwrap();
+            primary
+                .url
+                .replacen("https://", "wss://", 1)
+                .replacen("http://", "ws://", 1)
+        })
+    }
+
+    /// Run `call` against the endpoints in failover order until one succeeds.
+    ///
+    /// Only endpoint failures count against the health of an endpoint and move on
+    /// to the next one, any other error is returned right away.
+    pub async fn call<T, E, F, Fut>(&self, mut call: F) -> Result<T, E>
+    where
+        E: EndpointError,
+        F: FnMut(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut last_error = None;
+        for endpoint in self.ranked() {
+            match call(endpoint.client()).await {
+                Ok(value) => {
+                    endpoint.record(true);
+                    return Ok(value);
+                }
+                Err(e) if e.is_endpoint_failure() => {
+                    warn!("RPC request to {} failed: {}", endpoint.url, e);
+                    endpoint.record(false);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    endpoint.record(true);
+                    return Err(e);
+                }
+            }
+        }
+        Err(last_error.expect("RPC pool has at least one endpoint"))
+    }
+
+    /// Probe every endpoint periodically and update its health
+    pub fn spawn_health_checks(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                pool.probe().await;
+            }
+        })
+    }
+
+    async fn probe(&self) {
+        let probes = self.endpoints.iter().map(|endpoint| async move {
+            let started = Instant::now();
+            let slot = endpoint.client.get_slot().await;
+            (slot, started.elapsed())
+        });
+        let results = futures::future::join_all(probes).await;
+        let best_slot = results
+            .iter()
+            .filter_map(|(slot, _)| slot.as_ref().ok())
+            .max()
+            .copied()
+            .unwrap_or_default();
+
+        for (endpoint, (slot, latency)) in self.endpoints.iter().zip(results) {
+            let mut health = endpoint.health.lock().unwrap();
+            health.requests += 1;
+            match slot {
+                Ok(slot) => health.slot = slot,
+                Err(e) => {
+                    warn!("RPC health probe to {} failed: {}", endpoint.url, e);
+                    health.errors += 1;
+                }
+            }
+            health.latency = latency;
+
+            let slot_lag = best_slot.saturating_sub(health.slot);
+            let healthy = slot_lag <= MAX_SLOT_LAG
+                && health.latency <= MAX_LATENCY
+                && health.error_rate() <= MAX_ERROR_RATE;
+            if healthy != health.healthy {
+                warn!(
+                    "RPC endpoint {} is now {} (slot lag {}, latency {:?}, error rate {:.2})",
+                    endpoint.url,
+                    if healthy { "healthy" } else { "unhealthy" },
+                    slot_lag,
+                    health.latency,
+                    health.error_rate()
+                );
+            }
+            health.healthy = healthy;
+            // Halve the counters so the error rate follows recent behaviour
+            health.requests /= 2;
+            health.errors /= 2;
+        }
+    }
+
+    fn least_failing(&self) -> &RpcEndpoint {
+        self.endpoints
+            .iter()
+            .min_by(|a, b| {
+                let a = a.health.lock().unwrap().error_rate();
+                let b = b.health.lock().unwrap().error_rate();
+                a.total_cmp(&b)
+            })
+            .unwrap()
+    }
+}
+
+#[test]
+fn test_weighted_selection() {
+    let endpoint = |url: &str, weight| RpcEndpointConfig {
+        url: url.to_string(),
+        weight,
+    };
+    assert!(RpcPool::new(vec![endpoint("", 1)]).is_err());
+
+    let pool = RpcPool::new(vec![endpoint("http://a", 3), endpoint("http://b", 1)]).unwrap();
+    let picks: Vec<&str> = (0..8).map(|_| pool.select().url.as_str()).collect();
+    assert_eq!(picks.iter().filter(|url| **url == "http://a").count(), 6);
+
+    // Failover goes to the other endpoint once the selected one is unhealthy
+    pool.endpoints[0].health.lock().unwrap().healthy = false;
+    assert_eq!(pool.select().url, "http://b");
+    assert_eq!(pool.ranked().last().unwrap().url, "http://a");
+}
+
+#[tokio::test]
+async fn test_call_failover() {
+    use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+    use std::sync::atomic::AtomicU32;
+
+    let endpoint = |url: &str| RpcEndpointConfig {
+        url: url.to_string(),
+        weight: 1,
+    };
+    let pool = RpcPool::new(vec![endpoint("http://a"), endpoint("http://b")]).unwrap();
+    let attempts = &AtomicU32::new(0);
+
+    // A transport error fails over to every endpoint
+    let result: Result<(), ClientError> = pool
+        .call(|_| async move {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(ClientErrorKind::Io(std::io::ErrorKind::ConnectionReset.into()).into())
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts.swap(0, Ordering::Relaxed), 2);
+
+    // An error of the request itself is returned from the first endpoint and does
+    // not count against it
+    let result: Result<(), ClientError> = pool
+        .call(|_| async move {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                code: -32602,
+                message: "Invalid params".to_string(),
+                data: RpcResponseErrorData::Empty,
+            })
+            .into())
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    let errors: u32 = pool
+        .endpoints
+        .iter()
+        .map(|endpoint| endpoint.health.lock().unwrap().errors)
+        .sum();
+    assert_eq!(errors, 2);
+}
@@ -0,0 +1,399 @@
+use {
+    async_trait::async_trait,
+    log::{debug, error, info, warn},
+    solana_sdk::signature::Signature,
+    std::{str::FromStr, sync::Arc},
+};
+
+use crate::{
+    account_processer::PoolSummary,
+    events::{MeteoraEvent, Token, TokenAmount, TransactionReport},
+    message::TelegramService,
+    reconciler::Reconciler,
+};
+
+/// Consumer of the events emitted by the instruction processor
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Called for every event, as soon as its instruction is processed
+    async fn handle(&self, event: &MeteoraEvent);
+
+    /// Called once per transaction with LP or swap activity, after all its instructions
+    async fn handle_report(&self, _report: &TransactionReport) {}
+}
+
+/// Human readable line with the active bin and fees of a pool
+fn pool_state_line(summary: &PoolSummary) -> String {
+    let mut line = format!(
+        "Active Bin: {} (bin step {}, base fee {:.2} bps, protocol share {} bps)",
+        summary.active_id, summary.bin_step, summary.base_fee_bps, summary.protocol_share_bps
+    );
+    if let Some((amount_x, amount_y)) = summary.active_bin_amounts {
+        line.push_str(&format!(
+            "\nActive Bin Reserves: {} / {}",
+            amount_x, amount_y
+        ));
+    }
+    line
+}
+
+/// `a` / `b` for an optional pair of amounts
+fn amounts_line(amount_x: &Option<TokenAmount>, amount_y: &Option<TokenAmount>) -> String {
+    [amount_x, amount_y]
+        .iter()
+        .filter_map(|amount| amount.as_ref().map(|amount| amount.to_string()))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// One line per unfamiliar token with risky settings, empty when there are none
+fn risk_lines<'a>(tokens: impl IntoIterator<Item = &'a Token>) -> String {
+    let mut seen: Vec<&str> = Vec::new();
+    let mut lines = String::new();
+    for token in tokens {
+        if seen.contains(&token.mint.as_str()) {
+            continue;
+        }
+        seen.push(&token.mint);
+        let flags = token.risk_flags();
+        let Some(profile) = token.profile.as_ref().filter(|_| !flags.is_empty()) else {
+            continue;
+        };
+        lines.push_str(&format!(
+            "\nRisk {} ({}, supply {}): {}",
+            token,
+            token.mint,
+            profile.ui_supply(),
+            flags.join(", ")
+        ));
+    }
+    lines
+}
+
+/// Logs every event, with its JSON form at debug level
+pub struct LoggingSink;
+
+#[async_trait]
+impl EventSink for LoggingSink {
+    async fn handle(&self, event: &MeteoraEvent) {
+        debug!(
+            "Event: {}",
+            serde_json::to_string(event).unwrap_or("json encode error".to_string())
+        );
+        match event {
+            MeteoraEvent::LiquidityAdded(event) => {
+                info!(
+                    "{} by {}{} in {}: {} in bins {} to {} ({})",
+                    event.instruction,
+                    event.wallet,
+                    event.context.via(),
+                    event.pool,
+                    amounts_line(&event.amount_x, &event.amount_y),
+                    event.lower_bin_id,
+                    event.upper_bin_id,
+                    event.strategy
+                );
+            }
+            MeteoraEvent::LiquidityRemoved(event) => {
+                info!(
+                    "{} by {}{} in {}: {} withdrawn, fully exited: {}",
+                    event.instruction,
+                    event.wallet,
+                    event.context.via(),
+                    event.pool,
+                    amounts_line(&event.amount_x, &event.amount_y),
+                    event.fully_exited
+                );
+            }
+            MeteoraEvent::Swap(event) => {
+                info!(
+                    "{} by {}{} in {}: {} for {}",
+                    event.instruction,
+                    event.wallet,
+                    event.context.via(),
+                    event.pool,
+                    event.amount_in,
+                    event.amount_out
+                );
+            }
+            MeteoraEvent::Claim(event) => {
+                let amounts: Vec<String> = event
+                    .amounts
+                    .iter()
+                    .map(|amount| amount.to_string())
+                    .collect();
+                info!(
+                    "{} by {}{} from {}: {}",
+                    event.instruction,
+                    event.wallet,
+                    event.context.via(),
+                    event.position,
+                    amounts.join(" / ")
+                );
+                if let (Some(position_harvest), Some(wallet_harvest)) =
+                    (&event.position_harvest, &event.wallet_harvest)
+                {
+                    info!("  position harvested: {:?}", position_harvest);
+                    info!("  wallet harvested: {:?}", wallet_harvest);
+                }
+            }
+            MeteoraEvent::PositionOpened(event) => {
+                info!(
+                    "Position {} opened by {} in {}",
+                    event.position, event.wallet, event.pool
+                );
+            }
+            MeteoraEvent::PositionClosed(event) => {
+                info!(
+                    "Position {} closed by {} in {}",
+                    event.position, event.wallet, event.pool
+                );
+            }
+            MeteoraEvent::PoolCreated(event) => {
+                info!(
+                    "New pool {} ({} / {}, bin step {})",
+                    event.pool, event.token_x, event.token_y, event.bin_step
+                );
+            }
+            MeteoraEvent::AdminChange(event) => {
+                warn!(
+                    "Pool admin change {} on {}: {}",
+                    event.instruction, event.pool, event.details
+                );
+            }
+        }
+    }
+
+    async fn handle_report(&self, report: &TransactionReport) {
+        info!(
+            "Transaction {}: {} by {}{} in {} ({} events)",
+            report.signature,
+            report.kind,
+            report.wallets.join(", "),
+            report.via(),
+            report.pools.join(", "),
+            report.events.len()
+        );
+    }
+}
+
+/// Sends one Telegram message per transaction report, and alerts for new pools and
+/// admin changes
+pub struct TelegramSink {
+    telegram_service: Arc<TelegramService>,
+    reconciler: Option<Arc<Reconciler>>,
+}
+
+impl TelegramSink {
+    pub fn new(telegram_service: Arc<TelegramService>) -> Self {
+        Self {
+            telegram_service,
+            reconciler: None,
+        }
+    }
+
+    /// Remember sent alerts so a correction can repeat them if the transaction is dropped
+    pub fn with_reconciler(mut self, reconciler: Arc<Reconciler>) -> Self {
+        self.reconciler = Some(reconciler);
+        self
+    }
+
+    /// Telegram message section for `event`
+    fn message(event: &MeteoraEvent) -> String {
+        match event {
+            MeteoraEvent::LiquidityAdded(event) => {
+                let mut message = format!(
+                    "{}:\nPosition: {}\nAmounts: {}\nBins: {} to {}\nStrategy: {}",
+                    event.instruction,
+                    event.position,
+                    amounts_line(&event.amount_x, &event.amount_y),
+                    event.lower_bin_id,
+                    event.upper_bin_id,
+                    event.strategy
+                );
+                if let Some(active_id) = event.active_id {
+                    message.push_str(&format!("\nActive Bin: {}", active_id));
+                }
+                message
+            }
+            MeteoraEvent::LiquidityRemoved(event) => {
+                let mut message = format!("{}:\nPosition: {}", event.instruction, event.position);
+                if event.amount_x.is_some() || event.amount_y.is_some() {
+                    message.push_str(&format!(
+                        "\nAmounts: {}",
+                        amounts_line(&event.amount_x, &event.amount_y)
+                    ));
+                }
+                if event.fully_exited {
+                    message.push_str("\nFully exited");
+                }
+                message
+            }
+            MeteoraEvent::Claim(event) => {
+                let amounts: Vec<String> = event
+                    .amounts
+                    .iter()
+                    .map(|amount| amount.to_string())
+                    .collect();
+                format!(
+                    "{}:\nPosition: {}\nAmounts: {}",
+                    event.instruction,
+                    event.position,
+                    amounts.join(" / ")
+                )
+            }
+            MeteoraEvent::Swap(event) => {
+                let mut message = format!(
+                    "{}:\nAmount In: {}\nAmount Out: {}",
+                    event.instruction, event.amount_in, event.amount_out
+                );
+                if let (Some(fee), Some(protocol_fee)) = (&event.fee, &event.protocol_fee) {
+                    message.push_str(&format!("\nFee: {} (protocol fee {})", fee, protocol_fee));
+                }
+                if let (Some(start_bin_id), Some(end_bin_id)) =
+                    (event.start_bin_id, event.end_bin_id)
+                {
+                    message.push_str(&format!("\nBins: {} -> {}", start_bin_id, end_bin_id));
+                }
+                if let Some(pool_state) = &event.pool_state {
+                    message.push('\n');
+                    message.push_str(&pool_state_line(pool_state));
+                }
+                message
+            }
+            MeteoraEvent::PositionOpened(event) => {
+                let mut message = format!(
+                    "Position opened:\nPosition: {}\nOwner: {}\nPool: {}",
+                    event.position, event.wallet, event.pool
+                );
+                if let (Some(lower_bin_id), Some(upper_bin_id)) =
+                    (event.lower_bin_id, event.upper_bin_id)
+                {
+                    message.push_str(&format!("\nBins: {} to {}", lower_bin_id, upper_bin_id));
+                }
+                message
+            }
+            MeteoraEvent::PositionClosed(event) => {
+                let mut message = format!(
+                    "Position closed:\nPosition: {}\nOwner: {}\nPool: {}\nActivity: {} adds, {} removes, {} claims",
+                    event.position,
+                    event.wallet,
+                    event.pool,
+                    event.adds,
+                    event.removes,
+                    event.claims
+                );
+                if let Some(open_seconds) = event.open_seconds {
+                    message.push_str(&format!(
+                        "\nOpen for: {:.1} hours",
+                        open_seconds as f64 / 3600.0
+                    ));
+                }
+                message
+            }
+            MeteoraEvent::PoolCreated(event) => {
+                let mut message = format!(
+                    "New pool ({}):\nPool: {}\nToken X: {} ({})\nToken Y: {} ({})\nBin Step: {}",
+                    event.instruction,
+                    event.pool,
+                    event.token_x,
+                    event.token_x.mint,
+                    event.token_y,
+                    event.token_y.mint,
+                    event.bin_step
+                );
+                match event.base_fee_bps {
+                    Some(base_fee_bps) => {
+                        message.push_str(&format!("\nBase Fee: {:.2} bps", base_fee_bps))
+                    }
+                    None => message.push_str("\nBase Fee: unknown"),
+                }
+                if let Some(activation_point) = event.activation_point {
+                    message.push_str(&format!("\nActivation Point: {}", activation_point));
+                }
+                if let Some(price) = event.initial_price {
+                    message.push_str(&format!(
+                        "\nInitial Price: {} {} per {}",
+                        price, event.token_y, event.token_x
+                    ));
+                }
+                message
+            }
+            MeteoraEvent::AdminChange(event) => {
+                let mut message = format!(
+                    "Pool admin change ({}):\nPool: {}\nDetails: {}",
+                    event.instruction, event.pool, event.details
+                );
+                if let Some(pool_state) = &event.pool_state {
+                    message.push('\n');
+                    message.push_str(&pool_state_line(pool_state));
+                }
+                message
+            }
+        }
+    }
+
+    /// One message for all the events of a transaction
+    fn report_message(report: &TransactionReport) -> String {
+        let mut message = format!(
+            "{} by {}{}\nPool: {}\nSignature: {}",
+            report.kind,
+            report.wallets.join(", "),
+            report.via(),
+            report.pools.join(", "),
+            report.signature
+        );
+        // Vaults and bots act through their own accounts on behalf of the signer
+        if !report.wallets.contains(&report.signer) {
+            message.push_str(&format!("\nSigner: {}", report.signer));
+        }
+        message.push_str(&risk_lines(
+            report.events.iter().flat_map(|event| event.tokens()),
+        ));
+        for event in &report.events {
+            message.push_str("\n\n");
+            message.push_str(&Self::message(event));
+        }
+        message
+    }
+
+    /// Send `message`, remembered by the reconciler in case the transaction never
+    /// reaches finalized commitment
+    async fn send(&self, signature: &str, message: &str, high_severity: bool) {
+        let sent = if high_severity {
+            self.telegram_service.send_high_severity(message).await
+        } else {
+            self.telegram_service.send_message(message).await
+        };
+        if let Err(e) = sent {
+            error!("Failed to send Telegram alert: {}", e);
+            return;
+        }
+        if let (Some(reconciler), Ok(signature)) =
+            (&self.reconciler, Signature::from_str(signature))
+        {
+            reconciler.note_alert(&signature, message);
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for TelegramSink {
+    async fn handle(&self, event: &MeteoraEvent) {
+        // LP and swap activity is sent with the transaction report
+        let high_severity = match event {
+            MeteoraEvent::PoolCreated(_) => false,
+            MeteoraEvent::AdminChange(event) => event.watched,
+            _ => return,
+        };
+        let mut message = Self::message(event);
+        message.push_str(&risk_lines(event.tokens()));
+        self.send(&event.context().signature, &message, high_severity)
+            .await;
+    }
+
+    async fn handle_report(&self, report: &TransactionReport) {
+        let message = Self::report_message(report);
+        self.send(&report.signature, &message, false).await;
+    }
+}
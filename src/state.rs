@@ -0,0 +1,704 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+/// Number of applied claims remembered, to skip replayed transactions and undo dropped ones
+const RECENT_CLAIMS: usize = 1_000;
+
+/// Last transaction that went through the whole processing pipeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub signature: String,
+    pub slot: u64,
+}
+
+impl Checkpoint {
+    /// Parsed signature, `None` if the stored value is corrupted
+    pub fn signature(&self) -> Option<Signature> {
+        Signature::from_str(&self.signature).ok()
+    }
+}
+
+/// Fees and rewards harvested so far, per token mint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HarvestTotals {
+    #[serde(default)]
+    pub fees: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub rewards: BTreeMap<String, u64>,
+}
+
+impl HarvestTotals {
+    fn apply(&mut self, claim: &AppliedClaim, revert: bool) {
+        let totals = if claim.reward_index.is_some() {
+            &mut self.rewards
+        } else {
+            &mut self.fees
+        };
+        for (mint, amount) in &claim.amounts {
+            let total = totals.entry(mint.clone()).or_default();
+            *total = if revert {
+                total.saturating_sub(*amount)
+            } else {
+                total.saturating_add(*amount)
+            };
+        }
+    }
+}
+
+/// Change to a position recorded in the registry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PositionEvent {
+    Opened,
+    Added {
+        amount_x: u64,
+        amount_y: u64,
+    },
+    Removed {
+        amount_x: u64,
+        amount_y: u64,
+        fully_exited: bool,
+    },
+    Claimed {
+        reward_index: Option<u64>,
+        amounts: Vec<(String, u64)>,
+    },
+    Closed,
+}
+
+/// Position event with the transaction it happened in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionActivity {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub event: PositionEvent,
+}
+
+impl PositionActivity {
+    pub fn new(
+        signature: &Signature,
+        slot: u64,
+        block_time: Option<i64>,
+        event: PositionEvent,
+    ) -> Self {
+        Self {
+            signature: signature.to_string(),
+            slot,
+            block_time,
+            event,
+        }
+    }
+}
+
+/// Lifecycle of a position of a watched LP wallet, from open to close
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionRecord {
+    pub owner: String,
+    pub lb_pair: String,
+    /// Unknown when the position was only seen through its `PositionCreateEvent`
+    pub lower_bin_id: Option<i32>,
+    pub upper_bin_id: Option<i32>,
+    pub activity: Vec<PositionActivity>,
+}
+
+impl PositionRecord {
+    fn find(&self, event: &PositionEvent) -> Option<&PositionActivity> {
+        self.activity
+            .iter()
+            .find(|activity| activity.event == *event)
+    }
+
+    pub fn opened(&self) -> Option<&PositionActivity> {
+        self.find(&PositionEvent::Opened)
+    }
+
+    pub fn closed(&self) -> Option<&PositionActivity> {
+        self.find(&PositionEvent::Closed)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.closed().is_none()
+    }
+
+    /// Bin range, if known
+    pub fn bin_range(&self) -> Option<(i32, i32)> {
+        self.lower_bin_id.zip(self.upper_bin_id)
+    }
+}
+
+/// Claim added to the harvest totals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AppliedClaim {
+    signature: String,
+    position: String,
+    owner: String,
+    reward_index: Option<u64>,
+    amounts: Vec<(String, u64)>,
+}
+
+/// On-disk layout of the state file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PersistedState {
+    #[serde(default)]
+    checkpoint: Option<Checkpoint>,
+    /// Newest processed transaction known to be finalized, the fallback when a
+    /// transaction processed at `confirmed` is dropped
+    #[serde(default)]
+    finalized_checkpoint: Option<Checkpoint>,
+    /// Newest crawled signature per crawled address, e.g. `wallet:<pubkey>`
+    #[serde(default)]
+    stream_checkpoints: HashMap<String, Checkpoint>,
+    /// Harvested fees and rewards per position
+    #[serde(default)]
+    position_harvests: HashMap<String, HarvestTotals>,
+    /// Harvested fees and rewards per owner wallet
+    #[serde(default)]
+    wallet_harvests: HashMap<String, HarvestTotals>,
+    #[serde(default)]
+    recent_claims: VecDeque<AppliedClaim>,
+    /// Positions of watched LP wallets, open and closed
+    #[serde(default)]
+    positions: HashMap<String, PositionRecord>,
+}
+
+impl PersistedState {
+    fn apply_claim(&mut self, claim: &AppliedClaim, revert: bool) {
+        self.position_harvests
+            .entry(claim.position.clone())
+            .or_default()
+            .apply(claim, revert);
+        self.wallet_harvests
+            .entry(claim.owner.clone())
+            .or_default()
+            .apply(claim, revert);
+    }
+}
+
+/// File-backed store for everything the agent needs to remember across restarts.
+///
+/// Changes are kept in memory and written to the file by `flush`. The checkpoint
+/// and the applied claims are saved in the same snapshot, so transactions replayed
+/// after a crash are not counted twice.
+pub struct StateStore {
+    path: PathBuf,
+    state: Mutex<PersistedState>,
+    dirty: AtomicBool,
+}
+
+impl StateStore {
+    /// Open the store at `path`, starting empty if the file does not exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let state = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read state file {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse state file {}", path.display()))?
+        } else {
+            info!("State file {} not found, starting empty", path.display());
+            PersistedState::default()
+        };
+
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Last fully processed transaction, if any
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.state.lock().unwrap().checkpoint.clone()
+    }
+
+    /// Record `signature` as fully processed.
+    ///
+    /// Crawlers deliver every batch oldest first, so all the transactions before
+    /// `signature` were processed too and a restart resumes right after it. The
+    /// checkpoint never moves to an older slot.
+    pub fn record_processed(&self, signature: &Signature, slot: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.checkpoint.as_ref().is_some_and(|c| c.slot > slot) {
+            return;
+        }
+        state.checkpoint = Some(Checkpoint {
+            signature: signature.to_string(),
+            slot,
+        });
+        self.mark_dirty();
+    }
+
+    /// Newest signature crawled for the stream `key`, if any
+    pub fn stream_checkpoint(&self, key: &str) -> Option<Checkpoint> {
+        self.state
+            .lock()
+            .unwrap()
+            .stream_checkpoints
+            .get(key)
+            .cloned()
+    }
+
+    /// Record `signature` as the newest one crawled for the stream `key`
+    pub fn record_stream_checkpoint(&self, key: &str, signature: &Signature, slot: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .stream_checkpoints
+            .get(key)
+            .is_some_and(|c| c.slot > slot)
+        {
+            return;
+        }
+        state.stream_checkpoints.insert(
+            key.to_string(),
+            Checkpoint {
+                signature: signature.to_string(),
+                slot,
+            },
+        );
+        self.mark_dirty();
+    }
+
+    /// Record that `signature`, processed earlier, reached finalized commitment
+    pub fn record_finalized(&self, signature: &Signature, slot: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .finalized_checkpoint
+            .as_ref()
+            .is_some_and(|c| c.slot > slot)
+        {
+            return;
+        }
+        state.finalized_checkpoint = Some(Checkpoint {
+            signature: signature.to_string(),
+            slot,
+        });
+        self.mark_dirty();
+    }
+
+    /// Add fees (`reward_index` is `None`) or rewards claimed from `position` by
+    /// `owner` to the harvest totals, with the amounts claimed per token mint.
+    ///
+    /// Returns the new totals of the position and of the wallet, or `None` if this
+    /// claim was already counted, e.g. when a transaction is processed again after
+    /// a restart.
+    pub fn record_claim(
+        &self,
+        signature: &Signature,
+        position: &Pubkey,
+        owner: &Pubkey,
+        reward_index: Option<u64>,
+        amounts: &[(Pubkey, u64)],
+    ) -> Option<(HarvestTotals, HarvestTotals)> {
+        let claim = AppliedClaim {
+            signature: signature.to_string(),
+            position: position.to_string(),
+            owner: owner.to_string(),
+            reward_index,
+            amounts: amounts
+                .iter()
+                .map(|(mint, amount)| (mint.to_string(), *amount))
+                .collect(),
+        };
+        let mut state = self.state.lock().unwrap();
+        if state.recent_claims.iter().any(|applied| {
+            applied.signature == claim.signature
+                && applied.position == claim.position
+                && applied.reward_index == claim.reward_index
+        }) {
+            return None;
+        }
+
+        state.apply_claim(&claim, false);
+        state.recent_claims.push_back(claim);
+        if state.recent_claims.len() > RECENT_CLAIMS {
+            state.recent_claims.pop_front();
+        }
+        let totals = (
+            state.position_harvests[&position.to_string()].clone(),
+            state.wallet_harvests[&owner.to_string()].clone(),
+        );
+        self.mark_dirty();
+        Some(totals)
+    }
+
+    /// Register a position of a watched wallet as opened.
+    ///
+    /// Returns `true` the first time the position is seen. The instruction and
+    /// the event opening a position both call this, whichever comes last only
+    /// fills in the bin range if it was missing.
+    pub fn open_position(
+        &self,
+        position: &Pubkey,
+        owner: &Pubkey,
+        lb_pair: &Pubkey,
+        bin_range: Option<(i32, i32)>,
+        activity: PositionActivity,
+    ) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(record) = state.positions.get_mut(&position.to_string()) {
+            if record.bin_range().is_some() || bin_range.is_none() {
+                return false;
+            }
+            record.lower_bin_id = bin_range.map(|(lower, _)| lower);
+            record.upper_bin_id = bin_range.map(|(_, upper)| upper);
+            self.mark_dirty();
+            return false;
+        }
+        state.positions.insert(
+            position.to_string(),
+            PositionRecord {
+                owner: owner.to_string(),
+                lb_pair: lb_pair.to_string(),
+                lower_bin_id: bin_range.map(|(lower, _)| lower),
+                upper_bin_id: bin_range.map(|(_, upper)| upper),
+                activity: vec![PositionActivity {
+                    event: PositionEvent::Opened,
+                    ..activity
+                }],
+            },
+        );
+        self.mark_dirty();
+        true
+    }
+
+    /// Append an event to the lifecycle of a registered position.
+    ///
+    /// Returns the updated record, or `None` if the position is not registered or
+    /// the event was already recorded. A second `Closed` event is ignored as well.
+    pub fn record_position_activity(
+        &self,
+        position: &Pubkey,
+        activity: PositionActivity,
+    ) -> Option<PositionRecord> {
+        let mut state = self.state.lock().unwrap();
+        let Some(record) = state.positions.get_mut(&position.to_string()) else {
+            return None;
+        };
+        let duplicate = record.activity.iter().any(|recorded| {
+            recorded.signature == activity.signature && recorded.event == activity.event
+        });
+        if duplicate || (activity.event == PositionEvent::Closed && !record.is_open()) {
+            return None;
+        }
+        record.activity.push(activity);
+        let record = record.clone();
+        self.mark_dirty();
+        Some(record)
+    }
+
+    /// Lifecycle of a registered position
+    pub fn position(&self, position: &Pubkey) -> Option<PositionRecord> {
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .get(&position.to_string())
+            .cloned()
+    }
+
+    /// Registered positions owned by `owner`, open and closed
+    pub fn positions_of(&self, owner: &Pubkey) -> Vec<(String, PositionRecord)> {
+        let owner = owner.to_string();
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .iter()
+            .filter(|(_, record)| record.owner == owner)
+            .map(|(position, record)| (position.clone(), record.clone()))
+            .collect()
+    }
+
+    /// Pools with registered positions still open
+    pub fn open_position_pools(&self) -> Vec<Pubkey> {
+        let state = self.state.lock().unwrap();
+        let mut pools: Vec<Pubkey> = state
+            .positions
+            .values()
+            .filter(|record| record.is_open())
+            .filter_map(|record| Pubkey::from_str(&record.lb_pair).ok())
+            .collect();
+        pools.sort();
+        pools.dedup();
+        pools
+    }
+
+    /// Registered positions still open in `lb_pair`
+    pub fn open_positions_in(&self, lb_pair: &Pubkey) -> Vec<(String, PositionRecord)> {
+        let lb_pair = lb_pair.to_string();
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .iter()
+            .filter(|(_, record)| record.lb_pair == lb_pair && record.is_open())
+            .map(|(position, record)| (position.clone(), record.clone()))
+            .collect()
+    }
+
+    /// Undo the effects of a processed transaction that was dropped or forked out.
+    ///
+    /// Checkpoints pointing at it fall back to the last finalized transaction, or
+    /// are forgotten for crawled streams, which only know their own signatures.
+    /// Claims it made are taken out of the harvest totals, and its position events
+    /// out of the registry, forgetting positions it opened.
+    pub fn revert_transaction(&self, signature: &Signature) {
+        let signature = signature.to_string();
+        let mut state = self.state.lock().unwrap();
+        if state
+            .checkpoint
+            .as_ref()
+            .is_some_and(|c| c.signature == signature)
+        {
+            warn!("Rolling checkpoint back to the last finalized transaction");
+            state.checkpoint = state.finalized_checkpoint.clone();
+        }
+        state
+            .stream_checkpoints
+            .retain(|_, checkpoint| checkpoint.signature != signature);
+        let (reverted, kept) = std::mem::take(&mut state.recent_claims)
+            .into_iter()
+            .partition::<Vec<_>, _>(|claim| claim.signature == signature);
+        state.recent_claims = kept.into();
+        for claim in &reverted {
+            state.apply_claim(claim, true);
+        }
+        for record in state.positions.values_mut() {
+            record
+                .activity
+                .retain(|activity| activity.signature != signature);
+        }
+        state
+            .positions
+            .retain(|_, record| record.opened().is_some());
+        self.mark_dirty();
+    }
+
+    /// Forget all checkpoints, used for cold starts.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.checkpoint.take().is_some() || !state.stream_checkpoints.is_empty() {
+            warn!("Discarding stored checkpoints for cold start");
+        }
+        state.finalized_checkpoint = None;
+        state.stream_checkpoints.clear();
+        self.mark_dirty();
+    }
+
+    /// Note a change, written to the file by the next `flush`
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// Write the state to its file if it changed, from a snapshot so processing is
+    /// not held up by the file system
+    pub async fn flush(&self) {
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let path = self.path.clone();
+        let snapshot = self.state.lock().unwrap().clone();
+        let written = tokio::task::spawn_blocking(move || persist(&path, &snapshot))
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+        if let Err(e) = written {
+            warn!("Failed to persist state: {}", e);
+            self.mark_dirty();
+        }
+    }
+
+    /// Flush the state to its file periodically, so a burst of transactions costs
+    /// one write
+    pub fn spawn_persistence(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                store.flush().await;
+            }
+        })
+    }
+}
+
+/// Write `state` to a temporary file and atomically move it into place at `path`
+fn persist(path: &Path, state: &PersistedState) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let content = serde_json::to_string_pretty(state)?;
+    fs::write(&tmp_path, content)
+        .with_context(|| format!("Failed to write state file {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace state file {}", path.display()))?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_checkpoint_roundtrip() {
+    let path =
+        std::env::temp_dir().join(format!("meteora-agent-state-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let store = StateStore::open(&path).unwrap();
+    assert!(store.checkpoint().is_none());
+
+    let newer = Signature::new_unique();
+    store.record_processed(&newer, 200);
+    // An older slot must not rewind the checkpoint
+    store.record_processed(&Signature::new_unique(), 100);
+    // Nothing reaches the file before a flush
+    assert!(StateStore::open(&path).unwrap().checkpoint().is_none());
+    store.flush().await;
+
+    let reopened = StateStore::open(&path).unwrap();
+    let checkpoint = reopened.checkpoint().unwrap();
+    assert_eq!(checkpoint.slot, 200);
+    assert_eq!(checkpoint.signature(), Some(newer));
+
+    reopened.reset();
+    reopened.flush().await;
+    assert!(StateStore::open(&path).unwrap().checkpoint().is_none());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_position_lifecycle() {
+    let path = std::env::temp_dir().join(format!(
+        "meteora-agent-state-positions-{}.json",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let store = StateStore::open(&path).unwrap();
+
+    // Positions go through open, activity and close once, and dropped
+    // transactions are taken out of their lifecycle
+    let (position, owner, lb_pair) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let activity =
+        |signature: &Signature, event| PositionActivity::new(signature, 300, None, event);
+    let open = Signature::new_unique();
+    assert!(store.open_position(
+        &position,
+        &owner,
+        &lb_pair,
+        None,
+        activity(&open, PositionEvent::Opened)
+    ));
+    assert!(!store.open_position(
+        &position,
+        &owner,
+        &lb_pair,
+        Some((-5, 5)),
+        activity(&open, PositionEvent::Opened)
+    ));
+    assert_eq!(
+        store.position(&position).unwrap().bin_range(),
+        Some((-5, 5))
+    );
+    let close = Signature::new_unique();
+    assert!(
+        store
+            .record_position_activity(&position, activity(&close, PositionEvent::Closed))
+            .is_some()
+    );
+    assert!(
+        store
+            .record_position_activity(&position, activity(&close, PositionEvent::Closed))
+            .is_none()
+    );
+    assert!(store.open_positions_in(&lb_pair).is_empty());
+    store.revert_transaction(&close);
+    assert_eq!(store.open_positions_in(&lb_pair).len(), 1);
+    store.revert_transaction(&open);
+    assert!(store.positions_of(&owner).is_empty());
+
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_claim_totals() {
+    let path = std::env::temp_dir().join(format!(
+        "meteora-agent-state-claims-{}.json",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let store = StateStore::open(&path).unwrap();
+
+    // Claims add up per position and per wallet, once per transaction
+    let (position, owner, mint) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let claim = Signature::new_unique();
+    store
+        .record_claim(&claim, &position, &owner, None, &[(mint, 10)])
+        .unwrap();
+    assert!(
+        store
+            .record_claim(&claim, &position, &owner, None, &[(mint, 10)])
+            .is_none()
+    );
+    let (position_totals, wallet_totals) = store
+        .record_claim(
+            &Signature::new_unique(),
+            &position,
+            &owner,
+            None,
+            &[(mint, 5)],
+        )
+        .unwrap();
+    assert_eq!(position_totals.fees[&mint.to_string()], 15);
+    assert_eq!(wallet_totals, position_totals);
+    store.revert_transaction(&claim);
+    let (_, wallet_totals) = store
+        .record_claim(
+            &Signature::new_unique(),
+            &position,
+            &owner,
+            Some(0),
+            &[(mint, 1)],
+        )
+        .unwrap();
+    assert_eq!(wallet_totals.fees[&mint.to_string()], 5);
+    assert_eq!(wallet_totals.rewards[&mint.to_string()], 1);
+
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_checkpoint_rollback() {
+    let path = std::env::temp_dir().join(format!(
+        "meteora-agent-state-rollback-{}.json",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let store = StateStore::open(&path).unwrap();
+
+    // A dropped transaction rolls the checkpoint back to the finalized one
+    let confirmed = Signature::new_unique();
+    store.record_processed(&confirmed, 200);
+    let finalized = Signature::new_unique();
+    store.record_finalized(&finalized, 150);
+    store.revert_transaction(&confirmed);
+    assert_eq!(store.checkpoint().unwrap().signature(), Some(finalized));
+
+    let _ = fs::remove_file(&path);
+}
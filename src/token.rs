@@ -1,10 +1,22 @@
 use mpl_token_metadata::ID as TOKEN_METADATA_PROGRAM_ID;
 use mpl_token_metadata::accounts::Metadata;
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{account::Account, pubkey, pubkey::Pubkey};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use thiserror::Error;
+use tokio::sync::oneshot;
 
-use crate::utils::SOLANA_RPC;
+use crate::{
+    rpc_pool::{EndpointError, RpcPool},
+    utils::CONFIG,
+};
 
 #[derive(Error, Debug)]
 pub enum FetchMetadataError {
@@ -12,17 +24,47 @@ pub enum FetchMetadataError {
     RpcClientError(#[from] solana_client::client_error::ClientError),
     #[error("Invalid public key string: {0}")]
     InvalidPublicKey(#[from] solana_sdk::pubkey::ParsePubkeyError),
+    #[error("Metadata account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("Account {0} is not a token mint: {1}")]
+    InvalidMint(Pubkey, &'static str),
+    #[error("RPC request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Batched RPC request failed: {0}")]
+    BatchFailed(String),
+    #[error("No RPC endpoint configured")]
+    NoRpcEndpoint,
     #[error("Failed to deserialize metadata account: {0}")]
     DeserializationError(#[from] std::io::Error), // Borsh deserialize error wraps io::Error,
 }
 
+impl EndpointError for FetchMetadataError {
+    fn is_endpoint_failure(&self) -> bool {
+        match self {
+            FetchMetadataError::RpcClientError(e) => e.is_endpoint_failure(),
+            FetchMetadataError::Timeout(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Name and symbol of a single mint, lookups of the pipeline go through `resolve_token`
+#[allow(unused)]
 pub async fn get_token_metadata(
     mint_pubkey: Pubkey,
 ) -> Result<(String, String), FetchMetadataError> {
-    // 1. Create RPC client
-    let rpc_client = RpcClient::new(SOLANA_RPC.to_string());
+    // 1. Calculate Metadata PDA
+    let metadata_pda = metadata_pda(&mint_pubkey);
+
+    // 2. Get Metadata account information, retried on RPC errors and timeouts
+    let metadata_account = fetch_account(&metadata_pda).await?;
 
-    // 2. Calculate Metadata PDA
+    // 3. Deserialize the account and extract name and symbol
+    parse_metadata(&metadata_pda, metadata_account)
+}
+
+/// Metaplex Token Metadata PDA of `mint_pubkey`
+fn metadata_pda(mint_pubkey: &Pubkey) -> Pubkey {
     // Seeds for Metaplex Token Metadata PDA are "metadata", program ID, mint Pubkey
     let metadata_seeds = &[
         b"metadata".as_ref(),
@@ -32,33 +74,29 @@ pub async fn get_token_metadata(
     let (metadata_pda, _bump_seed) =
         Pubkey::find_program_address(metadata_seeds, &TOKEN_METADATA_PROGRAM_ID);
     log::debug!("Derived Metadata PDA: {}", metadata_pda);
+    metadata_pda
+}
 
-    // 3. Get Metadata account information
-    let metadata_account = rpc_client.get_account(&metadata_pda);
-
-    let account_data = match metadata_account {
-        Ok(account) => {
-            // Check if account owner is the Token Metadata Program (optional but recommended)
-            if account.owner != TOKEN_METADATA_PROGRAM_ID {
-                log::warn!(
-                    "Warning: Account owner ({}) is not the Token Metadata Program ID ({}).",
-                    account.owner,
-                    TOKEN_METADATA_PROGRAM_ID
-                );
-            }
-            account.data
-        }
-        Err(e) => {
-            // Other RPC errors
-            return Err(FetchMetadataError::RpcClientError(e));
-        }
+/// Name and symbol of a fetched Metadata account
+fn parse_metadata(
+    metadata_pda: &Pubkey,
+    metadata_account: Option<Account>,
+) -> Result<(String, String), FetchMetadataError> {
+    let Some(account) = metadata_account else {
+        return Err(FetchMetadataError::AccountNotFound(*metadata_pda));
     };
+    // Check if account owner is the Token Metadata Program (optional but recommended)
+    if account.owner != TOKEN_METADATA_PROGRAM_ID {
+        log::warn!(
+            "Warning: Account owner ({}) is not the Token Metadata Program ID ({}).",
+            account.owner,
+            TOKEN_METADATA_PROGRAM_ID
+        );
+    }
 
-    // 4. Deserialize account data
     // Metaplex's Metadata structure implements BorshDeserialize
-    let metadata = Metadata::from_bytes(&account_data)?;
+    let metadata = Metadata::from_bytes(&account.data)?;
 
-    // 5. Extract Name and Symbol
     // Note: Borsh serialized strings may have null bytes \0 at the end that need to be removed
     let name = metadata.name.trim_end_matches('\0').to_string();
     let symbol = metadata.symbol.trim_end_matches('\0').to_string();
@@ -66,6 +104,610 @@ pub async fn get_token_metadata(
     Ok((name, symbol))
 }
 
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Size of an SPL Token mint, also the base of a Token-2022 mint
+const MINT_LEN: usize = 82;
+/// Offsets in the mint layout: mint authority (`COption<Pubkey>`, 36 bytes), supply,
+/// decimals, the initialized flag and freeze authority
+const MINT_AUTHORITY_OFFSET: usize = 0;
+const MINT_SUPPLY_OFFSET: usize = 36;
+const MINT_DECIMALS_OFFSET: usize = 44;
+const MINT_FREEZE_AUTHORITY_OFFSET: usize = 46;
+/// Token-2022 mints with extensions are padded to the size of a token account, followed
+/// by the account type byte and the extensions as type-length-value entries
+const ACCOUNT_TYPE_OFFSET: usize = 165;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const EXTENSION_MINT_CLOSE_AUTHORITY: u16 = 3;
+const EXTENSION_DEFAULT_ACCOUNT_STATE: u16 = 6;
+const EXTENSION_NON_TRANSFERABLE: u16 = 9;
+const EXTENSION_INTEREST_BEARING_CONFIG: u16 = 10;
+const EXTENSION_PERMANENT_DELEGATE: u16 = 12;
+const EXTENSION_TRANSFER_HOOK: u16 = 14;
+const EXTENSION_TOKEN_METADATA: u16 = 19;
+/// `AccountState::Frozen`, as set by the default account state extension
+const ACCOUNT_STATE_FROZEN: u8 = 2;
+/// Year length used by the interest-bearing extension
+const SECONDS_PER_YEAR: f64 = 60.0 * 60.0 * 24.0 * 365.24;
+const ONE_IN_BASIS_POINTS: f64 = 10_000.0;
+
+/// Program owning a mint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenProgram {
+    Token,
+    Token2022,
+}
+
+/// Transfer fee of a Token-2022 mint, from the epoch it applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub basis_points: u16,
+}
+
+impl TransferFee {
+    /// Fee withheld on a transfer of `amount`, rounded up as the program does
+    pub fn fee(&self, amount: u64) -> u64 {
+        if self.basis_points == 0 || amount == 0 {
+            return 0;
+        }
+        let fee =
+            (amount as u128 * self.basis_points as u128).div_ceil(ONE_IN_BASIS_POINTS as u128);
+        fee.min(self.maximum_fee as u128) as u64
+    }
+}
+
+/// Transfer fee extension, the newer fee replacing the older one from its epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferFeeConfig {
+    pub older: TransferFee,
+    pub newer: TransferFee,
+}
+
+impl TransferFeeConfig {
+    /// Fee withheld on a transfer of `amount` during `epoch`
+    pub fn fee(&self, epoch: u64, amount: u64) -> u64 {
+        if epoch >= self.newer.epoch {
+            self.newer.fee(amount)
+        } else {
+            self.older.fee(amount)
+        }
+    }
+}
+
+/// Interest-bearing extension: UI amounts grow continuously at the configured rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterestBearingConfig {
+    pub initialization_timestamp: i64,
+    /// Average rate in basis points between initialization and the last rate update
+    pub pre_update_average_rate: i16,
+    pub last_update_timestamp: i64,
+    /// Rate in basis points since the last update
+    pub current_rate: i16,
+}
+
+impl InterestBearingConfig {
+    /// Factor turning raw amounts into UI amounts at `unix_timestamp`, before decimals
+    pub fn scale(&self, unix_timestamp: i64) -> f64 {
+        let exponent = |rate: i16, seconds: i64| {
+            rate as f64 * seconds as f64 / SECONDS_PER_YEAR / ONE_IN_BASIS_POINTS
+        };
+        let pre_update = exponent(
+            self.pre_update_average_rate,
+            self.last_update_timestamp - self.initialization_timestamp,
+        );
+        let post_update = exponent(
+            self.current_rate,
+            unix_timestamp - self.last_update_timestamp,
+        );
+        (pre_update + post_update).exp()
+    }
+}
+
+/// Mint account fields that change how raw amounts read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintInfo {
+    pub program: TokenProgram,
+    pub decimals: u8,
+    pub transfer_fee: Option<TransferFeeConfig>,
+    pub interest_bearing: Option<InterestBearingConfig>,
+}
+
+/// Parse a mint account of the SPL Token or Token-2022 program, with the Token-2022
+/// extensions affecting amounts
+pub fn parse_mint(mint: &Pubkey, account: &Account) -> Result<MintInfo, FetchMetadataError> {
+    let data = &account.data;
+    let program = match account.owner {
+        TOKEN_PROGRAM_ID => TokenProgram::Token,
+        TOKEN_2022_PROGRAM_ID => TokenProgram::Token2022,
+        _ => return Err(FetchMetadataError::InvalidMint(*mint, "unknown owner")),
+    };
+    if data.len() < MINT_LEN {
+        return Err(FetchMetadataError::InvalidMint(*mint, "account too short"));
+    }
+    let mut mint_info = MintInfo {
+        program,
+        decimals: data[MINT_DECIMALS_OFFSET],
+        transfer_fee: None,
+        interest_bearing: None,
+    };
+    if program == TokenProgram::Token {
+        return Ok(mint_info);
+    }
+    for (extension_type, value) in mint_extensions(mint, data)? {
+        match extension_type {
+            EXTENSION_TRANSFER_FEE_CONFIG => {
+                mint_info.transfer_fee = parse_transfer_fee_config(value)
+            }
+            EXTENSION_INTEREST_BEARING_CONFIG => {
+                mint_info.interest_bearing = parse_interest_bearing_config(value)
+            }
+            _ => {}
+        }
+    }
+    Ok(mint_info)
+}
+
+/// Extensions of a Token-2022 mint as (type, value) pairs, none for a mint without any
+fn mint_extensions<'a>(
+    mint: &Pubkey,
+    data: &'a [u8],
+) -> Result<Vec<(u16, &'a [u8])>, FetchMetadataError> {
+    let mut extensions = Vec::new();
+    if data.len() <= ACCOUNT_TYPE_OFFSET {
+        return Ok(extensions);
+    }
+    if data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_MINT {
+        return Err(FetchMetadataError::InvalidMint(
+            *mint,
+            "not a mint account type",
+        ));
+    }
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while let Some(header) = data.get(offset..offset + 4) {
+        let extension_type = u16::from_le_bytes([header[0], header[1]]);
+        let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let Some(value) = data.get(offset + 4..offset + 4 + length) else {
+            return Err(FetchMetadataError::InvalidMint(
+                *mint,
+                "truncated extension",
+            ));
+        };
+        extensions.push((extension_type, value));
+        offset += 4 + length;
+    }
+    Ok(extensions)
+}
+
+/// Name and symbol from the `TokenMetadata` extension of a Token-2022 mint
+pub fn parse_metadata_extension(mint: &Pubkey, account: &Account) -> Option<(String, String)> {
+    if account.owner != TOKEN_2022_PROGRAM_ID {
+        return None;
+    }
+    let (_, value) = mint_extensions(mint, &account.data)
+        .ok()?
+        .into_iter()
+        .find(|(extension_type, _)| *extension_type == EXTENSION_TOKEN_METADATA)?;
+    // Update authority and mint (2 x 32 bytes), then Borsh strings
+    let mut offset = 64;
+    let mut borsh_string = || {
+        let length = u32::from_le_bytes(value.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let string = std::str::from_utf8(value.get(offset + 4..offset + 4 + length)?).ok()?;
+        offset += 4 + length;
+        Some(string.trim_end_matches('\0').to_string())
+    };
+    let name = borsh_string()?;
+    let symbol = borsh_string()?;
+    Some((name, symbol))
+}
+
+/// Authorities (2 x 32 bytes) and withheld amount (8 bytes), then the older and newer fees
+fn parse_transfer_fee_config(value: &[u8]) -> Option<TransferFeeConfig> {
+    let transfer_fee = |offset: usize| {
+        Some(TransferFee {
+            epoch: u64::from_le_bytes(value.get(offset..offset + 8)?.try_into().ok()?),
+            maximum_fee: u64::from_le_bytes(value.get(offset + 8..offset + 16)?.try_into().ok()?),
+            basis_points: u16::from_le_bytes(value.get(offset + 16..offset + 18)?.try_into().ok()?),
+        })
+    };
+    Some(TransferFeeConfig {
+        older: transfer_fee(72)?,
+        newer: transfer_fee(90)?,
+    })
+}
+
+/// Rate authority (32 bytes), then timestamps and rates
+fn parse_interest_bearing_config(value: &[u8]) -> Option<InterestBearingConfig> {
+    Some(InterestBearingConfig {
+        initialization_timestamp: i64::from_le_bytes(value.get(32..40)?.try_into().ok()?),
+        pre_update_average_rate: i16::from_le_bytes(value.get(40..42)?.try_into().ok()?),
+        last_update_timestamp: i64::from_le_bytes(value.get(42..50)?.try_into().ok()?),
+        current_rate: i16::from_le_bytes(value.get(50..52)?.try_into().ok()?),
+    })
+}
+
+/// Decimals and amount-changing extensions of a fetched mint account
+fn parse_mint_account(
+    mint_pubkey: &Pubkey,
+    mint_account: Option<Account>,
+) -> Result<MintInfo, FetchMetadataError> {
+    match mint_account {
+        Some(account) => parse_mint(mint_pubkey, &account),
+        None => Err(FetchMetadataError::AccountNotFound(*mint_pubkey)),
+    }
+}
+
+/// Mainnet mints never flagged as unfamiliar: wrapped SOL, USDC and USDT
+const WELL_KNOWN_MINTS: [Pubkey; 3] = [
+    pubkey!("So11111111111111111111111111111111111111112"),
+    pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+    pubkey!("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCE8BenwNYB"),
+];
+
+/// Authorities, supply and extensions of a mint, what an LP should check before
+/// entering a token it does not know
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenProfile {
+    pub program: TokenProgram,
+    pub decimals: u8,
+    pub supply: u64,
+    /// `None` once renounced, no more tokens can be minted
+    pub mint_authority: Option<String>,
+    /// Can freeze any holder's token account
+    pub freeze_authority: Option<String>,
+    /// Update authority of the Metaplex metadata, or of the Token-2022 metadata extension
+    pub update_authority: Option<String>,
+    /// `None` when the token has no metadata
+    pub is_mutable: Option<bool>,
+    /// Program invoked on every transfer
+    pub transfer_hook: Option<String>,
+    /// Can transfer or burn tokens from any account
+    pub permanent_delegate: Option<String>,
+    pub transfer_fee: Option<TransferFeeConfig>,
+    /// Can close the mint once the supply is zero
+    pub close_authority: Option<String>,
+    /// New token accounts start frozen
+    pub default_frozen: bool,
+    pub non_transferable: bool,
+}
+
+impl TokenProfile {
+    /// Human readable warnings, empty for a token without any risky setting
+    pub fn risk_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if let Some(mint_authority) = &self.mint_authority {
+            flags.push(format!("mint authority not renounced ({})", mint_authority));
+        }
+        if let Some(freeze_authority) = &self.freeze_authority {
+            flags.push(format!("freeze authority ({})", freeze_authority));
+        }
+        if self.is_mutable == Some(true) {
+            flags.push("mutable metadata".to_string());
+        }
+        if let Some(transfer_hook) = &self.transfer_hook {
+            flags.push(format!("transfer hook ({})", transfer_hook));
+        }
+        if let Some(permanent_delegate) = &self.permanent_delegate {
+            flags.push(format!("permanent delegate ({})", permanent_delegate));
+        }
+        if let Some(transfer_fee) = &self.transfer_fee {
+            let basis_points = transfer_fee
+                .older
+                .basis_points
+                .max(transfer_fee.newer.basis_points);
+            if basis_points > 0 {
+                flags.push(format!("transfer fee up to {} bps", basis_points));
+            }
+        }
+        if self.close_authority.is_some() {
+            flags.push("mint close authority".to_string());
+        }
+        if self.default_frozen {
+            flags.push("accounts frozen by default".to_string());
+        }
+        if self.non_transferable {
+            flags.push("non-transferable".to_string());
+        }
+        flags
+    }
+
+    /// Supply in UI units
+    pub fn ui_supply(&self) -> f64 {
+        self.supply as f64 / 10f64.powi(self.decimals as i32)
+    }
+}
+
+/// Whether `mint_pubkey` is a major token or listed in the local token list, so its
+/// risk flags are not worth an alert
+pub fn is_familiar(mint_pubkey: &Pubkey) -> bool {
+    WELL_KNOWN_MINTS.contains(mint_pubkey) || TOKEN_LIST.contains_key(&mint_pubkey.to_string())
+}
+
+/// Risk profile of a mint account, completed with the authority of its Metaplex
+/// metadata account when it has one
+pub fn parse_profile(
+    mint: &Pubkey,
+    account: &Account,
+    metadata_account: Option<&Account>,
+) -> Result<TokenProfile, FetchMetadataError> {
+    let mint_info = parse_mint(mint, account)?;
+    let data = &account.data;
+    let mut profile = TokenProfile {
+        program: mint_info.program,
+        decimals: mint_info.decimals,
+        supply: u64::from_le_bytes(
+            data[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        ),
+        mint_authority: coption_pubkey(&data[MINT_AUTHORITY_OFFSET..]),
+        freeze_authority: coption_pubkey(&data[MINT_FREEZE_AUTHORITY_OFFSET..]),
+        update_authority: None,
+        is_mutable: None,
+        transfer_hook: None,
+        permanent_delegate: None,
+        transfer_fee: mint_info.transfer_fee,
+        close_authority: None,
+        default_frozen: false,
+        non_transferable: false,
+    };
+    if mint_info.program == TokenProgram::Token2022 {
+        for (extension_type, value) in mint_extensions(mint, data)? {
+            match extension_type {
+                EXTENSION_MINT_CLOSE_AUTHORITY => profile.close_authority = optional_pubkey(value),
+                EXTENSION_DEFAULT_ACCOUNT_STATE => {
+                    profile.default_frozen = value.first() == Some(&ACCOUNT_STATE_FROZEN)
+                }
+                EXTENSION_NON_TRANSFERABLE => profile.non_transferable = true,
+                EXTENSION_PERMANENT_DELEGATE => profile.permanent_delegate = optional_pubkey(value),
+                // Authority (32 bytes), then the hook program
+                EXTENSION_TRANSFER_HOOK => {
+                    profile.transfer_hook = value.get(32..).and_then(optional_pubkey)
+                }
+                // The metadata extension can be updated as long as it has an authority
+                EXTENSION_TOKEN_METADATA => {
+                    profile.update_authority = optional_pubkey(value);
+                    profile.is_mutable = Some(profile.update_authority.is_some());
+                }
+                _ => {}
+            }
+        }
+    }
+    let metaplex = metadata_account
+        .filter(|account| account.owner == TOKEN_METADATA_PROGRAM_ID)
+        .and_then(|account| Metadata::from_bytes(&account.data).ok());
+    if let Some(metadata) = metaplex {
+        profile.update_authority = Some(metadata.update_authority.to_string());
+        profile.is_mutable = Some(metadata.is_mutable);
+    }
+    Ok(profile)
+}
+
+/// `COption<Pubkey>` of the mint layout: a 4 byte tag then the key
+fn coption_pubkey(data: &[u8]) -> Option<String> {
+    match data.get(..4)? {
+        [1, 0, 0, 0] => optional_pubkey(data.get(4..)?),
+        _ => None,
+    }
+}
+
+/// `OptionalNonZeroPubkey` of the Token-2022 extensions, all zeros standing for `None`
+fn optional_pubkey(data: &[u8]) -> Option<String> {
+    let key: [u8; 32] = data.get(..32)?.try_into().ok()?;
+    (key != [0u8; 32]).then(|| Pubkey::new_from_array(key).to_string())
+}
+
+/// Time allowed for one account request to one endpoint
+const RPC_TIMEOUT: Duration = Duration::from_secs(5);
+/// Rounds over all the endpoints before giving up
+const MAX_ATTEMPTS: u32 = 3;
+/// Wait before the second round, doubled after every failed round
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Fetch an account through the shared non-blocking clients of the RPC pool
+async fn fetch_account(pubkey: &Pubkey) -> Result<Option<Account>, FetchMetadataError> {
+    rpc_request(
+        &format!("Account request for {}", pubkey),
+        |client| async move {
+            let response = client
+                .get_account_with_commitment(pubkey, client.commitment())
+                .await?;
+            Ok(response.value)
+        },
+    )
+    .await
+}
+
+/// Run `request` on the endpoints of the RPC pool in failover order, each attempt
+/// bounded by a timeout, and retry with backoff when all of them fail to serve it
+async fn rpc_request<T, F, Fut>(description: &str, mut request: F) -> Result<T, FetchMetadataError>
+where
+    F: FnMut(Arc<RpcClient>) -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
+{
+    let Some(rpc_pool) = RpcPool::try_global() else {
+        return Err(FetchMetadataError::NoRpcEndpoint);
+    };
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let result = rpc_pool
+            .call(|client| {
+                let response = request(client);
+                async move {
+                    match tokio::time::timeout(RPC_TIMEOUT, response).await {
+                        Ok(response) => Ok(response?),
+                        Err(_) => Err(FetchMetadataError::Timeout(RPC_TIMEOUT)),
+                    }
+                }
+            })
+            .await;
+        match result {
+            Err(e) if attempt < MAX_ATTEMPTS && e.is_endpoint_failure() => {
+                log::warn!("{} failed ({}), retrying in {:?}", description, e, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Entry of a Jupiter-style token list
+#[derive(Debug, Deserialize)]
+struct TokenListEntry {
+    address: String,
+    name: String,
+    symbol: String,
+}
+
+/// Name and symbol by mint from the token list file configured in `token_list`
+static TOKEN_LIST: Lazy<HashMap<String, (String, String)>> = Lazy::new(|| {
+    let Some(path) = &CONFIG.token_list else {
+        return HashMap::new();
+    };
+    match load_token_list(path) {
+        Ok(token_list) => {
+            log::info!("Loaded {} tokens from {}", token_list.len(), path);
+            token_list
+        }
+        Err(e) => {
+            log::warn!("Failed to load token list {}: {}", path, e);
+            HashMap::new()
+        }
+    }
+});
+
+fn load_token_list(path: &str) -> anyhow::Result<HashMap<String, (String, String)>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(parse_token_list(&content)?)
+}
+
+fn parse_token_list(content: &str) -> serde_json::Result<HashMap<String, (String, String)>> {
+    let entries: Vec<TokenListEntry> = serde_json::from_str(content)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.address, (entry.name, entry.symbol)))
+        .collect())
+}
+
+/// Name and symbol of `mint_pubkey` in the local token list
+fn token_list_metadata(mint_pubkey: &Pubkey) -> Option<(String, String)> {
+    TOKEN_LIST.get(&mint_pubkey.to_string()).cloned()
+}
+
+/// Time pending lookups are gathered before being resolved together
+const BATCH_WINDOW: Duration = Duration::from_millis(20);
+/// Keys per `getMultipleAccounts` request, the RPC limit
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Metadata (name, symbol) and mint account of a token, each with its own outcome, and
+/// its risk profile when the mint account could be read
+pub type TokenLookup = (
+    Result<(String, String), FetchMetadataError>,
+    Result<MintInfo, FetchMetadataError>,
+    Option<TokenProfile>,
+);
+
+/// Callers waiting for the next batch, by mint
+static PENDING_LOOKUPS: Lazy<Mutex<HashMap<Pubkey, Vec<oneshot::Sender<TokenLookup>>>>> =
+    Lazy::new(Default::default);
+
+/// Metadata and mint account of `mint_pubkey`, resolved together with the other
+/// lookups requested within the same short window
+pub async fn resolve_token(mint_pubkey: Pubkey) -> TokenLookup {
+    let (sender, receiver) = oneshot::channel();
+    let opens_batch = {
+        let mut pending = PENDING_LOOKUPS.lock().unwrap();
+        let opens_batch = pending.is_empty();
+        pending.entry(mint_pubkey).or_default().push(sender);
+        opens_batch
+    };
+    if opens_batch {
+        tokio::spawn(async {
+            tokio::time::sleep(BATCH_WINDOW).await;
+            resolve_pending().await;
+        });
+    }
+    receiver.await.unwrap_or_else(|_| {
+        let dropped = || FetchMetadataError::BatchFailed("lookup dropped".to_string());
+        (Err(dropped()), Err(dropped()), None)
+    })
+}
+
+/// Resolve every pending lookup: the metadata PDA and mint account of each mint,
+/// fetched with one `getMultipleAccounts` call per 100 keys
+async fn resolve_pending() {
+    let pending = std::mem::take(&mut *PENDING_LOOKUPS.lock().unwrap());
+    let mints: Vec<Pubkey> = pending.keys().copied().collect();
+    let keys: Vec<Pubkey> = mints
+        .iter()
+        .flat_map(|mint| [metadata_pda(mint), *mint])
+        .collect();
+    log::debug!("Resolving {} mints in one batch", mints.len());
+
+    // Pairs of keys stay in the same request, the batch size being even
+    let requests = keys.chunks(MAX_MULTIPLE_ACCOUNTS).map(|chunk| async move {
+        let description = format!("Batch of {} accounts", chunk.len());
+        let accounts = rpc_request(&description, |client| async move {
+            client.get_multiple_accounts(chunk).await
+        })
+        .await;
+        (chunk.len(), accounts)
+    });
+    let mut accounts: Vec<Result<Option<Account>, Arc<FetchMetadataError>>> = Vec::new();
+    for (len, result) in futures::future::join_all(requests).await {
+        match result {
+            Ok(chunk_accounts) => accounts.extend(chunk_accounts.into_iter().map(Ok)),
+            Err(e) => {
+                let e = Arc::new(e);
+                accounts.extend(std::iter::repeat_n(Err(e), len));
+            }
+        }
+    }
+
+    for (mint, account_pair) in mints.iter().zip(accounts.chunks(2)) {
+        let [metadata_account, mint_account] = account_pair else {
+            continue;
+        };
+        for sender in pending.get(mint).into_iter().flatten() {
+            let metadata = metadata_account
+                .clone()
+                .map_err(|e| shared_error(&e))
+                .and_then(|account| parse_metadata(&metadata_pda(mint), account))
+                .or_else(|e| {
+                    let on_mint = mint_account
+                        .as_ref()
+                        .ok()
+                        .and_then(|account| parse_metadata_extension(mint, account.as_ref()?));
+                    on_mint.or_else(|| token_list_metadata(mint)).ok_or(e)
+                });
+            let mint_info = mint_account
+                .clone()
+                .map_err(|e| shared_error(&e))
+                .and_then(|account| parse_mint_account(mint, account));
+            let profile = mint_account.as_ref().ok().and_then(|account| {
+                let metadata_account = metadata_account.as_ref().ok().and_then(Option::as_ref);
+                parse_profile(mint, account.as_ref()?, metadata_account).ok()
+            });
+            // The caller may have given up waiting
+            let _ = sender.send((metadata, mint_info, profile));
+        }
+    }
+}
+
+/// Copy of an error shared by the callers of one batch
+fn shared_error(error: &FetchMetadataError) -> FetchMetadataError {
+    match error {
+        FetchMetadataError::Timeout(timeout) => FetchMetadataError::Timeout(*timeout),
+        other => FetchMetadataError::BatchFailed(other.to_string()),
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test() {
     use std::str::FromStr;
@@ -73,6 +715,7 @@ async fn test() {
     // --- Configuration ---
     env_logger::init();
     dotenv::dotenv().ok();
+    RpcPool::init().unwrap();
 
     // Replace with the Mint address of the SPL Token you want to query
     let mint_address = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"; // USDC Mint Address example  
@@ -92,3 +735,151 @@ async fn test() {
         }
     }
 }
+
+#[test]
+fn test_parse_mint() {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0u8; MINT_LEN];
+    data[MINT_DECIMALS_OFFSET] = 6;
+    let mut account = Account {
+        lamports: 0,
+        data: data.clone(),
+        owner: TOKEN_PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let spl = parse_mint(&mint, &account).unwrap();
+    assert_eq!(spl.program, TokenProgram::Token);
+    assert_eq!(spl.decimals, 6);
+    assert_eq!(spl.transfer_fee, None);
+
+    // Token-2022 mint with a 1% transfer fee capped at 5000, raised to 2% at epoch 700,
+    // and a 5% interest rate since its creation
+    data.resize(ACCOUNT_TYPE_OFFSET, 0);
+    data.push(ACCOUNT_TYPE_MINT);
+    data.extend_from_slice(&EXTENSION_TRANSFER_FEE_CONFIG.to_le_bytes());
+    data.extend_from_slice(&108u16.to_le_bytes());
+    data.extend_from_slice(&[0u8; 72]);
+    for (epoch, maximum_fee, basis_points) in [(0u64, 5_000u64, 100u16), (700, 5_000, 200)] {
+        data.extend_from_slice(&epoch.to_le_bytes());
+        data.extend_from_slice(&maximum_fee.to_le_bytes());
+        data.extend_from_slice(&basis_points.to_le_bytes());
+    }
+    data.extend_from_slice(&EXTENSION_INTEREST_BEARING_CONFIG.to_le_bytes());
+    data.extend_from_slice(&52u16.to_le_bytes());
+    data.extend_from_slice(&[0u8; 32]);
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&500i16.to_le_bytes());
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&500i16.to_le_bytes());
+    account.data = data;
+    account.owner = TOKEN_2022_PROGRAM_ID;
+
+    let token_2022 = parse_mint(&mint, &account).unwrap();
+    assert_eq!(token_2022.program, TokenProgram::Token2022);
+    let transfer_fee = token_2022.transfer_fee.unwrap();
+    assert_eq!(transfer_fee.fee(600, 1_001), 11);
+    assert_eq!(transfer_fee.fee(600, 1_000_000), 5_000);
+    assert_eq!(transfer_fee.fee(700, 1_000), 20);
+    let interest_bearing = token_2022.interest_bearing.unwrap();
+    let one_year = SECONDS_PER_YEAR as i64;
+    assert!((interest_bearing.scale(one_year) - 0.05f64.exp()).abs() < 1e-6);
+
+    account.owner = Pubkey::new_unique();
+    assert!(parse_mint(&mint, &account).is_err());
+}
+
+#[test]
+fn test_metadata_fallbacks() {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0u8; ACCOUNT_TYPE_OFFSET];
+    data[MINT_DECIMALS_OFFSET] = 9;
+    data.push(ACCOUNT_TYPE_MINT);
+    let mut metadata = vec![0u8; 64];
+    for field in ["Wrapped Thing", "WTHG", "https://example.com/wthg.json"] {
+        metadata.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        metadata.extend_from_slice(field.as_bytes());
+    }
+    data.extend_from_slice(&EXTENSION_TOKEN_METADATA.to_le_bytes());
+    data.extend_from_slice(&(metadata.len() as u16).to_le_bytes());
+    data.extend_from_slice(&metadata);
+    let account = Account {
+        lamports: 0,
+        data,
+        owner: TOKEN_2022_PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    assert_eq!(
+        parse_metadata_extension(&mint, &account),
+        Some(("Wrapped Thing".to_string(), "WTHG".to_string()))
+    );
+    assert_eq!(parse_mint(&mint, &account).unwrap().decimals, 9);
+
+    let token_list = parse_token_list(
+        r#"[{"address": "So11111111111111111111111111111111111111112", "name": "Wrapped SOL",
+            "symbol": "SOL", "decimals": 9, "logoURI": "https://example.com/sol.png"}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        token_list.get("So11111111111111111111111111111111111111112"),
+        Some(&("Wrapped SOL".to_string(), "SOL".to_string()))
+    );
+}
+
+#[test]
+fn test_token_profile() {
+    let mint = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let mut data = vec![0u8; MINT_LEN];
+    data[MINT_AUTHORITY_OFFSET..MINT_AUTHORITY_OFFSET + 4].copy_from_slice(&1u32.to_le_bytes());
+    data[MINT_AUTHORITY_OFFSET + 4..MINT_AUTHORITY_OFFSET + 36].copy_from_slice(authority.as_ref());
+    data[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8]
+        .copy_from_slice(&1_000_000_000u64.to_le_bytes());
+    data[MINT_DECIMALS_OFFSET] = 6;
+    let mut account = Account {
+        lamports: 0,
+        data: data.clone(),
+        owner: TOKEN_PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let profile = parse_profile(&mint, &account, None).unwrap();
+    assert_eq!(profile.supply, 1_000_000_000);
+    assert_eq!(profile.ui_supply(), 1_000.0);
+    assert_eq!(profile.mint_authority, Some(authority.to_string()));
+    assert_eq!(profile.freeze_authority, None);
+    assert_eq!(profile.is_mutable, None);
+    assert_eq!(
+        profile.risk_flags(),
+        vec![format!("mint authority not renounced ({})", authority)]
+    );
+
+    // Token-2022 mint with a permanent delegate and a transfer hook
+    let delegate = Pubkey::new_unique();
+    let hook_program = Pubkey::new_unique();
+    data[MINT_AUTHORITY_OFFSET..MINT_AUTHORITY_OFFSET + 4].copy_from_slice(&0u32.to_le_bytes());
+    data.resize(ACCOUNT_TYPE_OFFSET, 0);
+    data.push(ACCOUNT_TYPE_MINT);
+    data.extend_from_slice(&EXTENSION_PERMANENT_DELEGATE.to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    data.extend_from_slice(delegate.as_ref());
+    data.extend_from_slice(&EXTENSION_TRANSFER_HOOK.to_le_bytes());
+    data.extend_from_slice(&64u16.to_le_bytes());
+    data.extend_from_slice(&[0u8; 32]);
+    data.extend_from_slice(hook_program.as_ref());
+    account.data = data;
+    account.owner = TOKEN_2022_PROGRAM_ID;
+
+    let profile = parse_profile(&mint, &account, None).unwrap();
+    assert_eq!(profile.mint_authority, None);
+    assert_eq!(
+        profile.risk_flags(),
+        vec![
+            format!("transfer hook ({})", hook_program),
+            format!("permanent delegate ({})", delegate),
+        ]
+    );
+    assert!(!is_familiar(&mint));
+    assert!(is_familiar(&WELL_KNOWN_MINTS[1]));
+}
@@ -0,0 +1,364 @@
+use anyhow::{Context, Result};
+use carbon_core::deserialize::CarbonDeserialize;
+use carbon_meteora_dlmm_decoder::accounts::lb_pair::LbPair;
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    rpc_pool::RpcPool,
+    token::{FetchMetadataError, MintInfo, TokenProfile, resolve_token},
+};
+
+/// Tokens kept in memory, the least recently used one is evicted beyond this
+const CAPACITY: usize = 10_000;
+/// Lifetime of a complete entry; mint extensions and metadata may still be updated
+const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Lifetime of an entry missing its metadata or mint account
+const NEGATIVE_TTL: Duration = Duration::from_secs(60 * 60);
+/// Accounts per `getMultipleAccounts` request during warm-up
+const WARM_UP_BATCH: usize = 100;
+
+/// Shared registry, set up once at startup by `TokenRegistry::init`
+static TOKEN_REGISTRY: OnceCell<Arc<TokenRegistry>> = OnceCell::new();
+
+/// What is known about a mint, as cached by the registry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenEntry {
+    /// Name and symbol from the Metaplex metadata account, `None` when the mint has none
+    pub metadata: Option<(String, String)>,
+    /// `None` when the mint account does not exist or is not a token mint
+    pub mint_info: Option<MintInfo>,
+    /// Authorities and risky extensions, missing from entries cached before profiles existed
+    #[serde(default)]
+    pub profile: Option<TokenProfile>,
+    /// Unix time of the lookup
+    pub fetched_at: u64,
+}
+
+impl TokenEntry {
+    pub fn symbol(&self) -> Option<&str> {
+        self.metadata.as_ref().map(|(_, symbol)| symbol.as_str())
+    }
+
+    fn is_fresh(&self, now: u64) -> bool {
+        let ttl = if self.metadata.is_some() && self.mint_info.is_some() && self.profile.is_some() {
+            TTL
+        } else {
+            NEGATIVE_TTL
+        };
+        now.saturating_sub(self.fetched_at) < ttl.as_secs()
+    }
+}
+
+/// Tokens by mint, evicting the least recently used one when full
+struct LruCache {
+    entries: HashMap<Pubkey, (TokenEntry, u64)>,
+    capacity: usize,
+    tick: u64,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, mint: &Pubkey) -> Option<TokenEntry> {
+        self.tick += 1;
+        let (entry, last_used) = self.entries.get_mut(mint)?;
+        *last_used = self.tick;
+        Some(entry.clone())
+    }
+
+    fn insert(&mut self, mint: Pubkey, entry: TokenEntry) {
+        self.tick += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&mint) {
+            let least_recent = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(mint, _)| *mint);
+            if let Some(least_recent) = least_recent {
+                self.entries.remove(&least_recent);
+            }
+        }
+        self.entries.insert(mint, (entry, self.tick));
+    }
+}
+
+/// Token metadata and mint accounts in front of the RPC lookups: an in-memory LRU
+/// snapshotted to a JSON file so it survives restarts
+pub struct TokenRegistry {
+    path: Option<PathBuf>,
+    cache: Mutex<LruCache>,
+    // Whether the cache changed since the last snapshot
+    dirty: AtomicBool,
+}
+
+impl TokenRegistry {
+    /// Registry backed by the file at `path`, loaded if it exists
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut cache = LruCache::new(CAPACITY);
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read token cache {}", path.display()))?;
+            let stored: HashMap<String, TokenEntry> = serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse token cache {}", path.display()))?;
+            let mut stored: Vec<(Pubkey, TokenEntry)> = stored
+                .into_iter()
+                .filter_map(|(mint, entry)| Some((Pubkey::from_str(&mint).ok()?, entry)))
+                .collect();
+            // Oldest first, so the most recent lookups survive if over capacity
+            stored.sort_by_key(|(_, entry)| entry.fetched_at);
+            for (mint, entry) in stored {
+                cache.insert(mint, entry);
+            }
+            info!(
+                "Loaded {} tokens from {}",
+                cache.entries.len(),
+                path.display()
+            );
+        }
+        Ok(Self {
+            path: Some(path),
+            cache: Mutex::new(cache),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Build the shared registry backed by the file at `path`
+    pub fn init(path: impl AsRef<Path>) -> Result<Arc<TokenRegistry>> {
+        let registry = Arc::new(Self::open(path)?);
+        Ok(TOKEN_REGISTRY.get_or_init(|| registry).clone())
+    }
+
+    /// The shared registry, memory only if `TokenRegistry::init` was not called
+    pub fn global() -> Arc<TokenRegistry> {
+        TOKEN_REGISTRY
+            .get_or_init(|| {
+                Arc::new(Self {
+                    path: None,
+                    cache: Mutex::new(LruCache::new(CAPACITY)),
+                    dirty: AtomicBool::new(false),
+                })
+            })
+            .clone()
+    }
+
+    /// Metadata and mint account of `mint`, from the cache while fresh. Mints without
+    /// a metadata account are cached too, lookups failing on RPC errors are not.
+    pub async fn get(&self, mint: &Pubkey) -> TokenEntry {
+        let now = unix_now();
+        let cached = self.cache.lock().unwrap().get(mint);
+        if let Some(entry) = cached.filter(|entry| entry.is_fresh(now)) {
+            return entry;
+        }
+
+        let (metadata, mint_info, profile) = resolve_token(*mint).await;
+        let mut transient = false;
+        let metadata = match metadata {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                warn!("Failed to fetch metadata of {}: {}", mint, e);
+                transient |= is_transient(&e);
+                None
+            }
+        };
+        let mint_info = match mint_info {
+            Ok(mint_info) => Some(mint_info),
+            Err(e) => {
+                warn!("Failed to fetch mint {}: {}", mint, e);
+                transient |= is_transient(&e);
+                None
+            }
+        };
+        let entry = TokenEntry {
+            metadata,
+            mint_info,
+            profile,
+            fetched_at: now,
+        };
+        if !transient {
+            self.insert(*mint, entry.clone());
+        }
+        entry
+    }
+
+    /// Cache `entry`, written to the file by the next `flush`
+    fn insert(&self, mint: Pubkey, entry: TokenEntry) {
+        self.cache.lock().unwrap().insert(mint, entry);
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// Write the cache to its file if it changed, from a snapshot so lookups are not
+    /// held up by the file system
+    pub async fn flush(&self) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let stored: HashMap<String, TokenEntry> = self
+            .cache
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|(mint, (entry, _))| (mint.to_string(), entry.clone()))
+            .collect();
+        let written = tokio::task::spawn_blocking(move || persist(&path, &stored))
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+        if let Err(e) = written {
+            warn!("Failed to persist token cache: {}", e);
+            self.dirty.store(true, Ordering::Release);
+        }
+    }
+
+    /// Flush the cache to its file periodically, so a burst of lookups costs one write
+    pub fn spawn_persistence(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                registry.flush().await;
+            }
+        })
+    }
+
+    /// Preload the mints of `pools`, read from their `LbPair` accounts
+    pub async fn warm_up(&self, pools: &[Pubkey]) {
+        let rpc_pool = RpcPool::global();
+        let mut mints = Vec::new();
+        for batch in pools.chunks(WARM_UP_BATCH) {
+            let accounts = rpc_pool
+                .call(|client| async move { client.get_multiple_accounts(batch).await })
+                .await;
+            let accounts = match accounts {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    warn!("Failed to fetch pools for token warm-up: {}", e);
+                    continue;
+                }
+            };
+            for lb_pair in accounts
+                .into_iter()
+                .flatten()
+                .filter_map(|account| LbPair::deserialize(&account.data))
+            {
+                for mint in [lb_pair.token_x_mint, lb_pair.token_y_mint] {
+                    if !mints.contains(&mint) {
+                        mints.push(mint);
+                    }
+                }
+            }
+        }
+        futures::future::join_all(mints.iter().map(|mint| self.get(mint))).await;
+        info!(
+            "Token registry warmed up with {} mints of {} pools",
+            mints.len(),
+            pools.len()
+        );
+    }
+}
+
+/// Replace the token cache file at `path` with `stored`
+fn persist(path: &Path, stored: &HashMap<String, TokenEntry>) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let content = serde_json::to_string(stored)?;
+    fs::write(&tmp_path, content)
+        .with_context(|| format!("Failed to write token cache {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace token cache {}", path.display()))?;
+    Ok(())
+}
+
+/// Errors worth retrying on the next lookup rather than caching
+fn is_transient(error: &FetchMetadataError) -> bool {
+    matches!(
+        error,
+        FetchMetadataError::RpcClientError(_)
+            | FetchMetadataError::Timeout(_)
+            | FetchMetadataError::BatchFailed(_)
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[tokio::test]
+async fn test_token_registry() {
+    let path = std::env::temp_dir().join("meteora_agent_token_registry_test.json");
+    let _ = fs::remove_file(&path);
+
+    let registry = TokenRegistry::open(&path).unwrap();
+    let mint = Pubkey::new_unique();
+    let now = unix_now();
+    let entry = TokenEntry {
+        metadata: Some(("USD Coin".to_string(), "USDC".to_string())),
+        mint_info: None,
+        profile: None,
+        fetched_at: now,
+    };
+    registry.insert(mint, entry.clone());
+    assert_eq!(
+        registry.cache.lock().unwrap().get(&mint),
+        Some(entry.clone())
+    );
+
+    // Incomplete entries expire sooner than complete ones
+    assert!(entry.is_fresh(now + NEGATIVE_TTL.as_secs() - 1));
+    assert!(!entry.is_fresh(now + NEGATIVE_TTL.as_secs()));
+
+    // Entries are written on flush and survive a restart
+    assert!(!path.exists());
+    registry.flush().await;
+    let reopened = TokenRegistry::open(&path).unwrap();
+    assert_eq!(reopened.cache.lock().unwrap().get(&mint), Some(entry));
+
+    // The least recently used token is evicted
+    let mut cache = LruCache::new(2);
+    let (first, second, third) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let empty = TokenEntry {
+        metadata: None,
+        mint_info: None,
+        profile: None,
+        fetched_at: now,
+    };
+    cache.insert(first, empty.clone());
+    cache.insert(second, empty.clone());
+    cache.get(&first);
+    cache.insert(third, empty);
+    assert!(cache.get(&first).is_some());
+    assert!(cache.get(&second).is_none());
+
+    let _ = fs::remove_file(&path);
+}
@@ -3,41 +3,173 @@ use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::{env, fs::File, io::BufReader};
 
+/// Comma separated list of RPC endpoints, merged with `rpc_endpoints` from the config file
 pub static SOLANA_RPC: Lazy<String> = Lazy::new(|| env::var("SOLANA_RPC").unwrap_or_default());
+/// Websocket endpoint for account subscriptions, derived from the primary RPC endpoint when unset
+pub static SOLANA_WS: Lazy<Option<String>> = Lazy::new(|| env::var("SOLANA_WS").ok());
+/// Stream DLMM account updates to keep live pool and position state, off by default
+pub static ACCOUNT_STREAMING: Lazy<bool> = Lazy::new(|| {
+    env::var("ACCOUNT_STREAMING")
+        .unwrap_or_default()
+        .parse()
+        .unwrap_or_default()
+});
 pub static CLIENT_ACCOUNT_FILTERING: Lazy<bool> = Lazy::new(|| {
     env::var("CLIENT_ACCOUNT_FILTERING")
         .unwrap_or_default()
         .parse()
         .unwrap_or_default()
 });
+/// Which transactions are crawled: `program` (every DLMM transaction), `wallets`
+/// (LP wallets only) or `pools` (configured pools only, the default when `pools` is set)
+pub static CRAWL_MODE: Lazy<String> = Lazy::new(|| {
+    env::var("CRAWL_MODE").unwrap_or_else(|_| {
+        if CONFIG.pools.is_empty() {
+            "program".to_string()
+        } else {
+            "pools".to_string()
+        }
+    })
+});
+/// Process and alert at confirmed commitment, reconciling at finalized afterwards
+pub static FAST_CONFIRMED: Lazy<bool> = Lazy::new(|| {
+    env::var("FAST_CONFIRMED")
+        .unwrap_or_default()
+        .parse()
+        .unwrap_or_default()
+});
+/// Path of the file where the crawler checkpoint is persisted
+pub static STATE_PATH: Lazy<String> =
+    Lazy::new(|| env::var("STATE_PATH").unwrap_or_else(|_| "state.json".to_string()));
+/// Path of the file where token metadata is cached across restarts
+pub static TOKEN_CACHE_PATH: Lazy<String> =
+    Lazy::new(|| env::var("TOKEN_CACHE_PATH").unwrap_or_else(|_| "token_cache.json".to_string()));
+/// Ignore the stored checkpoint and start crawling from the chain tip
+pub static COLD_START: Lazy<bool> = Lazy::new(|| {
+    env::var("COLD_START")
+        .unwrap_or_default()
+        .parse()
+        .unwrap_or_default()
+});
 
+/// Global static configuration read from `config.json`
+pub static CONFIG: Lazy<Config> = Lazy::new(|| read_config("config.json"));
 /// Global static collection of LP wallet addresses
-pub static LP_WALLETS: Lazy<Vec<String>> = Lazy::new(|| read_lp_wallets_config("config.json"));
-/// Configuration structure for LP wallets
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    lp_wallets: Vec<String>,
+pub static LP_WALLETS: Lazy<Vec<String>> = Lazy::new(|| CONFIG.lp_wallets.clone());
+
+/// Configuration file structure
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub lp_wallets: Vec<String>,
+    #[serde(default)]
+    pub rpc_endpoints: Vec<RpcEndpointConfig>,
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+    /// Alert on new pools passing these filters, no alerts when absent
+    #[serde(default)]
+    pub new_pools: Option<NewPoolsConfig>,
+    /// Jupiter-style token list file, names and symbols of mints without on-chain metadata
+    #[serde(default)]
+    pub token_list: Option<String>,
 }
 
-/// Reads LP wallet addresses from the configuration file
-fn read_lp_wallets_config(config_path: &str) -> Vec<String> {
+/// Filters for new pool alerts, an empty list accepts anything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewPoolsConfig {
+    /// Pools must have one of these mints on either side
+    #[serde(default)]
+    pub quote_mints: Vec<String>,
+    #[serde(default)]
+    pub bin_steps: Vec<u16>,
+    /// Accepted base fees in basis points, pools whose fee cannot be read are accepted
+    #[serde(default)]
+    pub fee_tiers_bps: Vec<f64>,
+}
+
+impl NewPoolsConfig {
+    /// Whether a new pool with these parameters should be announced
+    pub fn accepts(
+        &self,
+        token_x: &str,
+        token_y: &str,
+        bin_step: u16,
+        base_fee_bps: Option<f64>,
+    ) -> bool {
+        let quote_ok = self.quote_mints.is_empty()
+            || self
+                .quote_mints
+                .iter()
+                .any(|mint| mint == token_x || mint == token_y);
+        let bin_step_ok = self.bin_steps.is_empty() || self.bin_steps.contains(&bin_step);
+        let fee_ok = self.fee_tiers_bps.is_empty()
+            || base_fee_bps.is_none_or(|fee| {
+                self.fee_tiers_bps
+                    .iter()
+                    .any(|tier| (tier - fee).abs() < 1e-6)
+            });
+        quote_ok && bin_step_ok && fee_ok
+    }
+}
+
+/// Pool crawled on its own instead of the whole DLMM program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub lb_pair: String,
+    #[serde(default = "default_polling_interval_secs")]
+    pub polling_interval_secs: u64,
+}
+
+fn default_polling_interval_secs() -> u64 {
+    5
+}
+
+/// RPC endpoint with its share of the traffic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcEndpointConfig {
+    pub url: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Reads the configuration file, falling back to an empty configuration
+fn read_config(config_path: &str) -> Config {
     match File::open(config_path) {
         Ok(file) => {
             let reader = BufReader::new(file);
             match serde_json::from_reader::<_, Config>(reader) {
                 Ok(config) => {
                     info!("Loaded {} LP wallets from config", config.lp_wallets.len());
-                    config.lp_wallets
+                    config
                 }
                 Err(e) => {
                     error!("Error parsing config file: {}", e);
-                    Vec::new()
+                    Config::default()
                 }
             }
         }
         Err(e) => {
             error!("Error opening config file: {}", e);
-            Vec::new()
+            Config::default()
         }
     }
 }
+
+#[test]
+fn test_new_pools_filter() {
+    let filter = NewPoolsConfig {
+        quote_mints: vec!["USDC".to_string()],
+        bin_steps: vec![10, 20],
+        fee_tiers_bps: vec![10.0],
+    };
+    assert!(filter.accepts("SOL", "USDC", 10, Some(10.0)));
+    assert!(filter.accepts("USDC", "SOL", 20, None));
+    assert!(!filter.accepts("SOL", "BONK", 10, Some(10.0)));
+    assert!(!filter.accepts("SOL", "USDC", 25, Some(10.0)));
+    assert!(!filter.accepts("SOL", "USDC", 10, Some(25.0)));
+    assert!(NewPoolsConfig::default().accepts("SOL", "BONK", 1, None));
+}